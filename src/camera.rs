@@ -0,0 +1,80 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(pan_camera).add_system(zoom_camera);
+    }
+}
+
+/// How much one line of scrolling zooms in or out
+const ZOOM_STEP: f32 = 0.1;
+const MIN_SCALE: f32 = 0.2;
+const MAX_SCALE: f32 = 5.0;
+
+/// Converts a position on the window (from the bottom left corner) to world space
+pub fn screen_to_world(window: &Window, camera: &Transform, projection: &OrthographicProjection, screen: Vec2) -> Vec2 {
+    let center = Vec2::new(window.width(), window.height()) / 2.0;
+    camera.translation.truncate() + (screen - center) * projection.scale
+}
+
+/// Moves the camera while the middle mouse button is held down
+fn pan_camera(
+    windows: Res<Windows>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut last: Local<Option<Vec2>>,
+) {
+    let Some(position) = windows.get_primary().and_then(|wnd| wnd.cursor_position()) else { return };
+
+    if !mouse_input.pressed(MouseButton::Middle) {
+        *last = None;
+        return;
+    }
+
+    if let Some(last) = *last {
+        let (mut transform, projection) = camera.single_mut();
+        transform.translation -= ((position - last) * projection.scale).extend(0.0);
+    }
+    *last = Some(position);
+}
+
+/// UI nodes that take the scroll wheel for themselves while hovered
+#[derive(Component)]
+pub struct Scrollable;
+
+/// Zooms with the scroll wheel, keeping the point under the cursor in place
+fn zoom_camera(
+    windows: Res<Windows>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    mut scroll: EventReader<MouseWheel>,
+    scrollables: Query<&Interaction, With<Scrollable>>,
+) {
+    let lines: f32 = scroll
+        .iter()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 20.0,
+        })
+        .sum();
+
+    if lines == 0.0 || scrollables.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+
+    let wnd = windows.get_primary().unwrap();
+    let Some(position) = wnd.cursor_position() else { return };
+    let (mut transform, mut projection) = camera.single_mut();
+
+    let anchor = screen_to_world(wnd, &transform, &projection, position);
+    let old_scale = projection.scale;
+    projection.scale = (old_scale * (1.0 - ZOOM_STEP).powf(lines)).clamp(MIN_SCALE, MAX_SCALE);
+
+    let center = transform.translation.truncate();
+    let center = anchor - (anchor - center) * projection.scale / old_scale;
+    transform.translation = center.extend(transform.translation.z);
+}
@@ -0,0 +1,1316 @@
+//! Headless simulation model.
+//!
+//! A [`Circuit`] holds the same information as the `Gate`, `Edge` and `Node` entities of the editor,
+//! but without any dependency on Bevy, so circuits can be built, simulated and tested from plain Rust.
+//!
+//! Timing follows the editor: gates settle instantly, and every wire takes one tick to carry its value.
+//!
+//! Pins carry between 1 and [`MAX_WIDTH`] bits, stored in the low bits of a `u64`. Each bit is a [`Logic`] level:
+//! besides 0 and 1, it can be floating (Z) when nothing drives it, or unknown (X), see [`Signal`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    ops::RangeInclusive,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Number of inputs a gate like And or Xor can have
+pub const VARIADIC_INPUTS: RangeInclusive<usize> = 2..=16;
+
+/// Number of bits a pin can carry
+pub const MAX_WIDTH: u8 = 64;
+
+/// Keeps the bits of a value that fit in the given width
+pub fn mask(value: u64, width: u8) -> u64 {
+    if width >= MAX_WIDTH {
+        value
+    } else {
+        value & ((1 << width) - 1)
+    }
+}
+
+/// Level of a single bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Logic {
+    Low,
+    High,
+    /// High impedance: nothing drives the bit
+    Z,
+    /// Unknown, like when drivers disagree or a gate reads a floating input
+    X,
+}
+
+impl Logic {
+    pub fn as_char(&self) -> char {
+        match self {
+            Logic::Low => '0',
+            Logic::High => '1',
+            Logic::Z => 'Z',
+            Logic::X => 'X',
+        }
+    }
+}
+
+/// Value of a pin, bit by bit. Bits set in `z` are floating and bits set in `x` unknown, the others are given by `bits`.
+/// A bit is only ever set in one of the three.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Signal {
+    pub bits: u64,
+    pub z: u64,
+    pub x: u64,
+}
+
+impl Signal {
+    /// Every bit driven to the level given in `bits`
+    pub const fn known(bits: u64) -> Self {
+        Self { bits, z: 0, x: 0 }
+    }
+
+    /// Nothing driven, like an input of a gate that isn't connected
+    pub fn floating(width: u8) -> Self {
+        Self { bits: 0, z: mask(!0, width), x: 0 }
+    }
+
+    pub fn unknown(width: u8) -> Self {
+        Self { bits: 0, z: 0, x: mask(!0, width) }
+    }
+
+    /// Builds a signal from the bits set high and the bits known to be low, the others being unknown
+    fn from_levels(high: u64, low: u64, width: u8) -> Self {
+        Self { bits: high, z: 0, x: mask(!(high | low), width) }
+    }
+
+    /// Bits that are floating or unknown
+    pub fn undefined(&self) -> u64 {
+        self.z | self.x
+    }
+
+    /// Bits known to be low
+    fn low(&self) -> u64 {
+        !self.bits & !self.undefined()
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.undefined() == 0
+    }
+
+    /// Whether any bit is known to be high
+    pub fn is_high(&self) -> bool {
+        self.bits != 0
+    }
+
+    pub fn bit(&self, idx: u8) -> Logic {
+        match (self.bits >> idx & 1, self.z >> idx & 1, self.x >> idx & 1) {
+            (_, 1, _) => Logic::Z,
+            (_, _, 1) => Logic::X,
+            (1, _, _) => Logic::High,
+            _ => Logic::Low,
+        }
+    }
+
+    /// Moves the bit at `from` to `to`, dropping the others
+    fn move_bit(&self, from: u8, to: u8) -> Self {
+        let bit = |mask: u64| (mask >> from & 1) << to;
+        Self { bits: bit(self.bits), z: bit(self.z), x: bit(self.x) }
+    }
+
+    /// Keeps the bits that fit in the given width
+    pub fn mask(self, width: u8) -> Self {
+        Self { bits: mask(self.bits, width), z: mask(self.z, width), x: mask(self.x, width) }
+    }
+
+    /// Writes the signal in hexadecimal, or bit by bit from the highest one if some aren't known
+    pub fn format(&self, width: u8) -> String {
+        match width {
+            1 => self.bit(0).as_char().to_string(),
+            _ if self.is_known() => format!("0x{:0digits$X}", self.bits, digits = (width as usize).div_ceil(4)),
+            _ => (0..width).rev().map(|idx| self.bit(idx).as_char()).collect(),
+        }
+    }
+}
+
+impl From<u64> for Signal {
+    fn from(bits: u64) -> Self {
+        Self::known(bits)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GateType {
+    And,
+    Or,
+    Xor,
+    Not,
+    Nand,
+    Nor,
+    Xnor,
+    Buffer,
+    /// Drives its output with its input while enabled, and leaves it floating otherwise, so several of them can share a bus
+    TriState,
+    /// Level-sensitive latch, set by S and reset by R
+    SrLatch,
+    /// Stores D on the rising edge of the clock
+    DFlipFlop,
+    /// Sets on J, resets on K and toggles on both, on the rising edge of the clock
+    JkFlipFlop,
+    /// Toggles on the rising edge of the clock while T is high
+    TFlipFlop,
+    /// Square wave generator, without any input
+    Clock(ClockConfig),
+    /// Splits a bus into its bits, from the lowest one at the top
+    Splitter,
+    /// Gathers bits into a bus, from the lowest one at the top
+    Merger,
+    /// Component made by the user, indexed into the components of the file (see `save::ComponentSave`).
+    /// Its pins come from the side panels of its definition, and it is simulated by inlining it.
+    Custom(usize),
+}
+
+/// Timing of a clock, counted in simulation ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClockConfig {
+    pub period: u32,
+    /// Number of ticks the clock stays high at the start of each period, which sets the duty cycle
+    pub high: u32,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self { period: 10, high: 5 }
+    }
+}
+
+impl ClockConfig {
+    pub fn is_valid(&self) -> bool {
+        self.period > 0 && self.high <= self.period
+    }
+
+    /// Value of the output after the given number of ticks into the period
+    pub fn is_high(&self, ticks: u32) -> bool {
+        ticks < self.high
+    }
+}
+
+/// Internal state of a sequential gate. Combinational gates ignore it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GateState {
+    /// Stored bit, shown on the Q output
+    pub q: bool,
+    /// Value of the clock during the last update, to detect rising edges
+    pub clock: bool,
+    /// Ticks elapsed in the current period of a clock
+    pub ticks: u32,
+    /// Whether a clock is stopped
+    pub paused: bool,
+}
+
+impl GateType {
+    pub fn as_str(&self) -> &'static str {
+        use GateType::*;
+        match self {
+            And => "And",
+            Or => "Or",
+            Xor => "Xor",
+            Not => "Not",
+            Nand => "Nand",
+            Nor => "Nor",
+            Xnor => "Xnor",
+            Buffer => "Buffer",
+            TriState => "Tri-state",
+            SrLatch => "SR",
+            DFlipFlop => "D FF",
+            JkFlipFlop => "JK FF",
+            TFlipFlop => "T FF",
+            Clock(_) => "Clock",
+            Splitter => "Split",
+            Merger => "Merge",
+            Custom(_) => "Custom",
+        }
+    }
+
+    /// Whether the outputs depend on a [`GateState`] and not only on the inputs
+    pub fn is_sequential(&self) -> bool {
+        use GateType::*;
+        matches!(self, SrLatch | DFlipFlop | JkFlipFlop | TFlipFlop | Clock(_))
+    }
+
+    /// Names of the inputs, in order. Flip-flops end with the optional asynchronous set and reset inputs.
+    pub fn input_names(&self) -> &'static [&'static str] {
+        use GateType::*;
+        match self {
+            And | Or | Xor | Not | Nand | Nor | Xnor | Buffer | Clock(_) | Splitter | Merger | Custom(_) => &[],
+            TriState => &["A", "En"],
+            SrLatch => &["S", "R"],
+            DFlipFlop => &["D", "Clk", "S", "R"],
+            JkFlipFlop => &["J", "K", "Clk", "S", "R"],
+            TFlipFlop => &["T", "Clk", "S", "R"],
+        }
+    }
+
+    /// Names of the outputs, empty if the gate only has one
+    pub fn output_names(&self) -> &'static [&'static str] {
+        match self {
+            GateType::Clock(_) => &[],
+            kind if kind.is_sequential() => &["Q", "Q\u{304}"],
+            _ => &[],
+        }
+    }
+
+    /// Number of bits a gate of this type can work on. Combinational gates apply bitwise across a bus.
+    pub fn width_range(&self) -> RangeInclusive<u8> {
+        use GateType::*;
+        match self {
+            And | Or | Xor | Not | Nand | Nor | Xnor | Buffer | TriState => 1..=MAX_WIDTH,
+            SrLatch | DFlipFlop | JkFlipFlop | TFlipFlop | Clock(_) | Custom(_) => 1..=1,
+            Splitter | Merger => 2..=MAX_WIDTH,
+        }
+    }
+
+    pub fn default_width(&self) -> u8 {
+        *self.width_range().start()
+    }
+
+    /// Widths of the inputs of a gate with the given number of inputs and width
+    ///
+    /// # Panics
+    ///
+    /// For custom components, whose pins come from their definition. The same goes for
+    /// [`GateType::output_widths`] and [`GateType::input_range`].
+    pub fn input_widths(&self, num_inputs: usize, width: u8) -> Vec<u8> {
+        match self {
+            GateType::Merger => vec![1; num_inputs],
+            // The enable input is a single bit
+            GateType::TriState => vec![width, 1],
+            GateType::Custom(_) => panic!("custom components take their pins from their definition"),
+            _ => vec![width; num_inputs],
+        }
+    }
+
+    /// Widths of the outputs of a gate with the given width
+    pub fn output_widths(&self, width: u8) -> Vec<u8> {
+        match self {
+            GateType::Splitter => vec![1; width as usize],
+            GateType::Custom(_) => panic!("custom components take their pins from their definition"),
+            _ => vec![width; self.output_names().len().max(1)],
+        }
+    }
+
+    /// Number of inputs a gate of this type and width can have
+    pub fn input_range(&self, width: u8) -> RangeInclusive<usize> {
+        use GateType::*;
+        match self {
+            And | Or | Xor | Nand | Nor | Xnor => VARIADIC_INPUTS,
+            Splitter => 1..=1,
+            // One input per bit
+            Merger => width as usize..=width as usize,
+            Not | Buffer => 1..=1,
+            SrLatch | TriState => 2..=2,
+            // Set and reset can be left out, or only set
+            DFlipFlop | TFlipFlop => 2..=4,
+            JkFlipFlop => 3..=5,
+            Clock(_) => 0..=0,
+            Custom(_) => panic!("custom components take their pins from their definition"),
+        }
+    }
+
+    pub fn default_inputs(&self, width: u8) -> usize {
+        *self.input_range(width).start()
+    }
+
+    /// Computes the output of a one-bit combinational gate from the values of its inputs
+    ///
+    /// # Panics
+    ///
+    /// If the gate is sequential, or a splitter or merger, see [`GateType::update`] instead.
+    pub fn evaluate(&self, inputs: &[bool]) -> bool {
+        let inputs: Vec<_> = inputs.iter().map(|&v| v as u64).collect();
+        self.evaluate_bits(&inputs) & 1 == 1
+    }
+
+    /// Same as [`GateType::evaluate`], on every bit of the inputs at once. The result must be masked to the width of the gate.
+    pub fn evaluate_bits(&self, inputs: &[u64]) -> u64 {
+        use GateType::*;
+        match self {
+            And => inputs.iter().fold(!0, |acc, v| acc & v),
+            Or => inputs.iter().fold(0, |acc, v| acc | v),
+            // Odd parity, which is what chaining two-input Xors gives
+            Xor => inputs.iter().fold(0, |acc, v| acc ^ v),
+            Not => !inputs[0],
+            Nand => !And.evaluate_bits(inputs),
+            Nor => !Or.evaluate_bits(inputs),
+            Xnor => !Xor.evaluate_bits(inputs),
+            Buffer => inputs[0],
+            TriState | SrLatch | DFlipFlop | JkFlipFlop | TFlipFlop | Clock(_) | Splitter | Merger | Custom(_) => {
+                panic!("{} gates aren't bitwise, use `GateType::update`", self.as_str())
+            }
+        }
+    }
+
+    /// Same as [`GateType::evaluate_bits`], on signals of the given width. Known inputs that decide the output on
+    /// their own win, like a low input of an And gate, and the output is unknown otherwise. Floating inputs read as unknown.
+    pub fn evaluate_signals(&self, inputs: &[Signal], width: u8) -> Signal {
+        use GateType::*;
+        let all = |f: fn(&Signal) -> u64| inputs.iter().fold(!0, |acc, input| acc & f(input));
+        let any = |f: fn(&Signal) -> u64| inputs.iter().fold(0, |acc, input| acc | f(input));
+
+        let (high, low) = match self {
+            And | Nand => (all(|s| s.bits), any(Signal::low)),
+            Or | Nor => (any(|s| s.bits), all(Signal::low)),
+            Xor | Xnor => {
+                let parity = inputs.iter().fold(0, |acc, input| acc ^ input.bits);
+                let undefined = any(Signal::undefined);
+                (parity & !undefined, !parity & !undefined)
+            }
+            Not | Buffer => (inputs[0].bits, inputs[0].low()),
+            _ => panic!("{} gates aren't bitwise, use `GateType::update`", self.as_str()),
+        };
+
+        match self {
+            Nand | Nor | Xnor | Not => Signal::from_levels(mask(low, width), high, width),
+            _ => Signal::from_levels(mask(high, width), low, width),
+        }
+    }
+
+    /// Computes the outputs of a gate of the given width, updating the state of sequential gates.
+    /// Sequential gates output unknown values while any input they need is, and keep their state until they are known again.
+    /// Their asynchronous set and reset inputs are optional, and are inactive while floating.
+    pub fn update(&self, inputs: &[Signal], width: u8, state: &mut GateState) -> Vec<Signal> {
+        use GateType::*;
+        match self {
+            Splitter => return (0..width).map(|bit| inputs[0].move_bit(bit, 0)).collect(),
+            Merger => {
+                let bits = inputs.iter().enumerate().map(|(bit, input)| input.move_bit(0, bit as u8));
+                let merged = bits.fold(Signal::default(), |acc, bit| Signal {
+                    bits: acc.bits | bit.bits,
+                    z: acc.z | bit.z,
+                    x: acc.x | bit.x,
+                });
+                return vec![merged];
+            }
+            TriState => {
+                let output = match inputs[1].bit(0) {
+                    Logic::High => Buffer.evaluate_signals(&inputs[..1], width),
+                    Logic::Low => Signal::floating(width),
+                    Logic::Z | Logic::X => Signal::unknown(width),
+                };
+                return vec![output];
+            }
+            Clock(config) => return vec![Signal::known(config.is_high(state.ticks) as u64)],
+            Custom(_) => panic!("custom components are inlined, they have no behavior of their own"),
+            _ if !self.is_sequential() => return vec![self.evaluate_signals(inputs, width)],
+            _ => {}
+        }
+
+        // Set and reset come right after the clock
+        let asynchronous = match self {
+            SrLatch => 2..2,
+            DFlipFlop | TFlipFlop => 2..4,
+            _ => 3..5,
+        };
+        let inputs: Option<Vec<_>> = inputs
+            .iter()
+            .enumerate()
+            .map(|(idx, input)| match input.bit(0) {
+                Logic::Low => Some(false),
+                Logic::High => Some(true),
+                Logic::Z if asynchronous.contains(&idx) => Some(false),
+                Logic::Z | Logic::X => None,
+            })
+            .collect();
+        let Some(inputs) = inputs else { return vec![Signal::unknown(1); 2] };
+
+        // Flip-flops all have their clock right before set and reset
+        let (clock_idx, q) = match self {
+            SrLatch => (None, inputs[0] || state.q),
+            DFlipFlop => (Some(1), inputs[0]),
+            JkFlipFlop => (Some(2), (inputs[0] && !state.q) || (!inputs[1] && state.q)),
+            TFlipFlop => (Some(1), inputs[0] != state.q),
+            _ => unreachable!(),
+        };
+
+        match clock_idx {
+            Some(idx) => {
+                let clock = inputs[idx];
+                if clock && !state.clock {
+                    state.q = q;
+                }
+                state.clock = clock;
+
+                let (set, reset) = (inputs.get(idx + 1), inputs.get(idx + 2));
+                if set == Some(&true) {
+                    state.q = true;
+                }
+                if reset == Some(&true) {
+                    state.q = false;
+                }
+            }
+            // Reset wins when both inputs of the latch are high
+            None => state.q = q && !inputs[1],
+        }
+
+        vec![Signal::known(state.q as u64), Signal::known(!state.q as u64)]
+    }
+
+    /// Advances running clocks by one simulation tick
+    pub fn tick(&self, state: &mut GateState) {
+        if let GateType::Clock(config) = self {
+            if !state.paused {
+                state.ticks = (state.ticks + 1) % config.period;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PinId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GateId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WireId(usize);
+
+#[derive(Debug, Clone)]
+pub struct Gate {
+    pub kind: GateType,
+    pub width: u8,
+    pub inputs: Vec<PinId>,
+    pub outputs: Vec<PinId>,
+    pub state: GateState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wire {
+    pub from: PinId,
+    pub to: PinId,
+}
+
+/// Named pins through which a circuit is driven and observed, in the order of the side panels
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interface {
+    pub inputs: Vec<(String, PinId)>,
+    pub outputs: Vec<(String, PinId)>,
+}
+
+impl Interface {
+    /// Interface of the given pins, with their names
+    pub fn named(inputs: &[(&str, PinId)], outputs: &[(&str, PinId)]) -> Self {
+        let named = |pins: &[(&str, PinId)]| pins.iter().map(|&(name, pin)| (name.to_string(), pin)).collect();
+        Self { inputs: named(inputs), outputs: named(outputs) }
+    }
+}
+
+/// How a pin driven by several wires gets its value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resolution {
+    /// The drivers must agree, otherwise the pin is in conflict and the bits they disagree on are unknown
+    #[default]
+    Exclusive,
+    /// Bits are set if any driver sets them
+    WiredOr,
+    /// Bits are set if every driver sets them
+    WiredAnd,
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Exclusive => "Exclusive",
+            Resolution::WiredOr => "Wired OR",
+            Resolution::WiredAnd => "Wired AND",
+        }
+    }
+
+    /// The next mode, to cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            Resolution::Exclusive => Resolution::WiredOr,
+            Resolution::WiredOr => Resolution::WiredAnd,
+            Resolution::WiredAnd => Resolution::Exclusive,
+        }
+    }
+
+    /// Combines the values of the drivers of a pin of the given width. Floating drivers are ignored, like disabled
+    /// tri-state buffers sharing a bus, so the pin only floats where none of them drives it.
+    pub fn resolve(self, values: &[Signal], width: u8) -> Signal {
+        let any = |f: fn(&Signal) -> u64| values.iter().fold(0, |acc, value| acc | f(value));
+        let (high, low, unknown) = (any(|value| value.bits), any(Signal::low), any(|value| value.x));
+        let floating = mask(!(high | low | unknown), width);
+
+        let (high, low) = match self {
+            Resolution::Exclusive => (high & !low & !unknown, low & !high & !unknown),
+            Resolution::WiredOr => (high, low & !high & !unknown),
+            Resolution::WiredAnd => (high & !low & !unknown, low),
+        };
+        let resolved = Signal::from_levels(high, low | floating, width);
+        Signal { z: floating, ..resolved }
+    }
+
+    /// Whether drivers try to set the same bit to different levels, which no resolution but [`Resolution::Exclusive`]
+    /// considers a conflict
+    pub fn conflicts(self, values: &[Signal]) -> bool {
+        let any = |f: fn(&Signal) -> u64| values.iter().fold(0, |acc, value| acc | f(value));
+        self == Resolution::Exclusive && any(|value| value.bits) & any(Signal::low) != 0
+    }
+}
+
+/// Returned by [`Circuit::run_until_stable`] when the circuit still changes after the allowed number of steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unstable;
+
+/// Returned by [`Circuit::connect`] when the pins don't have the same width
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WidthMismatch {
+    pub from: u8,
+    pub to: u8,
+}
+
+/// Finds the groups of nodes that can all reach each other through `successors`, with Tarjan's algorithm.
+/// Only groups forming a cycle are returned: several nodes, or a single one leading to itself.
+pub fn find_cycles<T: Copy + Eq + Hash>(nodes: &[T], successors: impl Fn(T) -> Vec<T>) -> Vec<Vec<T>> {
+    struct Search<T, F> {
+        successors: F,
+        /// Order of visit and lowest order reachable of each visited node
+        order: HashMap<T, (usize, usize)>,
+        stack: Vec<T>,
+        on_stack: HashSet<T>,
+        cycles: Vec<Vec<T>>,
+    }
+
+    impl<T: Copy + Eq + Hash, F: Fn(T) -> Vec<T>> Search<T, F> {
+        fn lower(&mut self, node: T, low: usize) {
+            let entry = self.order.get_mut(&node).unwrap();
+            entry.1 = entry.1.min(low);
+        }
+
+        fn visit(&mut self, node: T) {
+            let order = self.order.len();
+            self.order.insert(node, (order, order));
+            self.stack.push(node);
+            self.on_stack.insert(node);
+
+            let next = (self.successors)(node);
+            for &succ in &next {
+                if !self.order.contains_key(&succ) {
+                    self.visit(succ);
+                    self.lower(node, self.order[&succ].1);
+                } else if self.on_stack.contains(&succ) {
+                    self.lower(node, self.order[&succ].0);
+                }
+            }
+
+            // The node is the first one visited of its group, which is on the stack above it
+            let (order, low) = self.order[&node];
+            if order == low {
+                let start = self.stack.iter().rposition(|&n| n == node).unwrap();
+                let group = self.stack.split_off(start);
+                for n in &group {
+                    self.on_stack.remove(n);
+                }
+                if group.len() > 1 || next.contains(&node) {
+                    self.cycles.push(group);
+                }
+            }
+        }
+    }
+
+    let mut search = Search {
+        successors,
+        order: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        cycles: vec![],
+    };
+    for &node in nodes {
+        if !search.order.contains_key(&node) {
+            search.visit(node);
+        }
+    }
+    search.cycles
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Circuit {
+    pins: Vec<Signal>,
+    widths: Vec<u8>,
+    // Removed gates and wires leave a hole, so that ids stay valid
+    gates: Vec<Option<Gate>>,
+    wires: Vec<Option<Wire>>,
+    /// Shared by the custom components inlined in the circuit
+    resolution: Resolution,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a free-standing one-bit pin, used for the inputs and outputs of the circuit
+    pub fn add_pin(&mut self) -> PinId {
+        self.add_bus(1)
+    }
+
+    /// Adds a free-standing pin carrying the given number of bits
+    pub fn add_bus(&mut self, width: u8) -> PinId {
+        assert!((1..=MAX_WIDTH).contains(&width), "pins can't have {width} bits");
+
+        self.pins.push(Signal::floating(width));
+        self.widths.push(width);
+        PinId(self.pins.len() - 1)
+    }
+
+    /// Adds a gate along with its input and output pins, and the default state
+    pub fn add_gate(&mut self, kind: GateType) -> GateId {
+        let width = kind.default_width();
+        self.add_gate_with(kind, kind.default_inputs(width), width)
+    }
+
+    pub fn add_gate_with_inputs(&mut self, kind: GateType, num_inputs: usize) -> GateId {
+        self.add_gate_with(kind, num_inputs, kind.default_width())
+    }
+
+    pub fn add_gate_with(&mut self, kind: GateType, num_inputs: usize, width: u8) -> GateId {
+        assert!(kind.width_range().contains(&width), "{} gates can't have {width} bits", kind.as_str());
+        assert!(
+            kind.input_range(width).contains(&num_inputs),
+            "{} gates can't have {num_inputs} inputs",
+            kind.as_str()
+        );
+
+        let inputs = kind.input_widths(num_inputs, width).into_iter().map(|w| self.add_bus(w)).collect();
+        let outputs = kind.output_widths(width).into_iter().map(|w| self.add_bus(w)).collect();
+
+        self.gates.push(Some(Gate { kind, width, inputs, outputs, state: GateState::default() }));
+        GateId(self.gates.len() - 1)
+    }
+
+    pub fn remove_gate(&mut self, id: GateId) -> Option<Gate> {
+        let gate = self.gates.get_mut(id.0)?.take()?;
+
+        // Wires attached to the gate don't lead anywhere anymore
+        let mut released = vec![];
+        for wire in self.wires.iter_mut() {
+            if let Some(Wire { from, to }) = *wire {
+                let is_pin = |pin| gate.outputs.contains(pin) || gate.inputs.contains(pin);
+                if is_pin(&from) || is_pin(&to) {
+                    *wire = None;
+                    released.push(to);
+                }
+            }
+        }
+        for pin in released {
+            self.release(pin);
+        }
+
+        Some(gate)
+    }
+
+    pub fn gate(&self, id: GateId) -> Option<&Gate> {
+        self.gates.get(id.0)?.as_ref()
+    }
+
+    /// Copies of several gates, to wire their pins while the circuit is borrowed mutably. Panics if one was removed.
+    pub fn gates_of<const N: usize>(&self, ids: [GateId; N]) -> [Gate; N] {
+        ids.map(|id| self.gate(id).expect("the gate was removed").clone())
+    }
+
+    pub fn gates(&self) -> impl Iterator<Item = (GateId, &Gate)> {
+        self.gates
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, gate)| Some((GateId(idx), gate.as_ref()?)))
+    }
+
+    pub fn set_state(&mut self, id: GateId, state: GateState) {
+        if let Some(Some(gate)) = self.gates.get_mut(id.0) {
+            gate.state = state;
+        }
+    }
+
+    /// Adds a wire carrying the value of `from` to `to`, which must have the same width
+    pub fn connect(&mut self, from: PinId, to: PinId) -> Result<WireId, WidthMismatch> {
+        let (from_width, to_width) = (self.width(from), self.width(to));
+        if from_width != to_width {
+            return Err(WidthMismatch { from: from_width, to: to_width });
+        }
+
+        self.wires.push(Some(Wire { from, to }));
+        Ok(WireId(self.wires.len() - 1))
+    }
+
+    pub fn remove_wire(&mut self, id: WireId) -> Option<Wire> {
+        let wire = self.wires.get_mut(id.0)?.take()?;
+        self.release(wire.to);
+        Some(wire)
+    }
+
+    /// Leaves a pin that lost its last driver floating, since stepping only writes the pins that wires drive
+    fn release(&mut self, pin: PinId) {
+        if !self.wires().any(|(_, wire)| wire.to == pin) {
+            self.pins[pin.0] = Signal::floating(self.widths[pin.0]);
+        }
+    }
+
+    pub fn wire(&self, id: WireId) -> Option<&Wire> {
+        self.wires.get(id.0)?.as_ref()
+    }
+
+    pub fn wires(&self) -> impl Iterator<Item = (WireId, &Wire)> {
+        self.wires
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, wire)| Some((WireId(idx), wire.as_ref()?)))
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+    }
+
+    /// Values carried to each pin driven by wires
+    fn drivers(&self) -> HashMap<PinId, Vec<Signal>> {
+        let mut drivers: HashMap<_, Vec<_>> = HashMap::new();
+        for wire in self.wires.iter().flatten() {
+            drivers.entry(wire.to).or_default().push(self.pins[wire.from.0]);
+        }
+        drivers
+    }
+
+    /// Pins driven by wires that carry values the resolution can't combine
+    pub fn conflicts(&self) -> Vec<PinId> {
+        let mut conflicts: Vec<_> = self
+            .drivers()
+            .into_iter()
+            .filter(|(_, values)| self.resolution.conflicts(values))
+            .map(|(pin, _)| pin)
+            .collect();
+        conflicts.sort();
+        conflicts
+    }
+
+    pub fn width(&self, pin: PinId) -> u8 {
+        self.widths[pin.0]
+    }
+
+    /// Whether any bit of the pin is known to be set
+    pub fn get(&self, pin: PinId) -> bool {
+        self.pins[pin.0].is_high()
+    }
+
+    pub fn set(&mut self, pin: PinId, value: bool) {
+        self.pins[pin.0] = Signal::known(value as u64);
+    }
+
+    /// Bits of the pin known to be set
+    pub fn value(&self, pin: PinId) -> u64 {
+        self.pins[pin.0].bits
+    }
+
+    /// Drives every bit of a pin, dropping those that don't fit in its width
+    pub fn set_value(&mut self, pin: PinId, value: u64) {
+        self.set_signal(pin, Signal::known(value));
+    }
+
+    pub fn signal(&self, pin: PinId) -> Signal {
+        self.pins[pin.0]
+    }
+
+    pub fn set_signal(&mut self, pin: PinId, signal: Signal) {
+        self.pins[pin.0] = signal.mask(self.widths[pin.0]);
+    }
+
+    /// Advances the simulation by one tick, and returns whether any pin or the state of any gate changed
+    pub fn step(&mut self) -> bool {
+        let changed = self.propagate();
+        self.tick_clocks() || changed
+    }
+
+    /// Updates every gate and wire once, without moving the clocks forward, and returns whether any pin or the state
+    /// of any gate changed
+    fn propagate(&mut self) -> bool {
+        let before = self.pins.clone();
+        let mut state_changed = false;
+
+        for gate in self.gates.iter_mut().flatten() {
+            let state = gate.state;
+            let inputs: Vec<_> = gate.inputs.iter().map(|&pin| self.pins[pin.0]).collect();
+            let outputs = gate.kind.update(&inputs, gate.width, &mut gate.state);
+            for (pin, value) in gate.outputs.iter().zip(outputs) {
+                self.pins[pin.0] = value;
+            }
+            state_changed |= state != gate.state;
+        }
+
+        // Wires read the values from the start of the tick, like they would if they all ran at the same time
+        for (pin, values) in self.drivers() {
+            self.pins[pin.0] = self.resolution.resolve(&values, self.widths[pin.0]);
+        }
+
+        state_changed || before != self.pins
+    }
+
+    /// Moves the clocks forward by one tick, and returns whether any of them changed
+    pub fn tick_clocks(&mut self) -> bool {
+        let mut ticked = false;
+        for gate in self.gates.iter_mut().flatten() {
+            let state = gate.state;
+            gate.kind.tick(&mut gate.state);
+            ticked |= state != gate.state;
+        }
+        ticked
+    }
+
+    /// Loops of gates feeding each other without going through a sequential gate, which may never settle
+    pub fn combinational_cycles(&self) -> Vec<Vec<GateId>> {
+        let combinational: Vec<_> =
+            self.gates().filter(|(_, gate)| !gate.kind.is_sequential()).map(|(id, _)| id).collect();
+        let pins = |pins: fn(&Gate) -> &Vec<PinId>| -> HashMap<_, _> {
+            combinational
+                .iter()
+                .flat_map(|&id| pins(self.gates[id.0].as_ref().unwrap()).iter().map(move |&pin| (pin, id)))
+                .collect()
+        };
+        let (inputs, outputs) = (pins(|gate| &gate.inputs), pins(|gate| &gate.outputs));
+
+        // Built once, so the search doesn't go through every wire for each gate
+        let mut successors: HashMap<_, Vec<_>> = HashMap::new();
+        for (_, wire) in self.wires() {
+            if let (Some(&from), Some(&to)) = (outputs.get(&wire.from), inputs.get(&wire.to)) {
+                successors.entry(from).or_default().push(to);
+            }
+        }
+        find_cycles(&combinational, |id| successors.get(&id).cloned().unwrap_or_default())
+    }
+
+    /// Propagates the values of the pins until they don't change anymore, all at once and without moving the clocks
+    /// forward, and returns the number of rounds it took
+    pub fn settle(&mut self, max_rounds: usize) -> Result<usize, Unstable> {
+        for rounds in 0..max_rounds {
+            if !self.propagate() {
+                return Ok(rounds);
+            }
+        }
+        Err(Unstable)
+    }
+
+    /// Steps the simulation until no pin changes anymore, and returns the number of steps it took
+    pub fn run_until_stable(&mut self, max_steps: usize) -> Result<usize, Unstable> {
+        for steps in 0..max_steps {
+            if !self.step() {
+                return Ok(steps);
+            }
+        }
+        Err(Unstable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truth_table(kind: GateType) -> Vec<bool> {
+        match kind.default_inputs(1) {
+            1 => [false, true].iter().map(|&a| kind.evaluate(&[a])).collect(),
+            _ => [(false, false), (false, true), (true, false), (true, true)]
+                .iter()
+                .map(|&(a, b)| kind.evaluate(&[a, b]))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn gate_truth_tables() {
+        use GateType::*;
+        assert_eq!(truth_table(And), [false, false, false, true]);
+        assert_eq!(truth_table(Or), [false, true, true, true]);
+        assert_eq!(truth_table(Xor), [false, true, true, false]);
+        assert_eq!(truth_table(Not), [true, false]);
+        assert_eq!(truth_table(Nand), [true, true, true, false]);
+        assert_eq!(truth_table(Nor), [true, false, false, false]);
+        assert_eq!(truth_table(Xnor), [true, false, false, true]);
+        assert_eq!(truth_table(Buffer), [false, true]);
+    }
+
+    #[test]
+    fn wide_gates_reduce_over_every_input() {
+        use GateType::*;
+        let inputs = [true, true, false, true];
+        assert!(!And.evaluate(&inputs));
+        assert!(And.evaluate(&[true; 16]));
+        assert!(Or.evaluate(&inputs));
+        assert!(!Or.evaluate(&[false; 5]));
+        assert!(Xor.evaluate(&inputs));
+        assert!(!Xor.evaluate(&[true, true, false, false]));
+        assert!(!Xnor.evaluate(&inputs));
+        assert!(Nand.evaluate(&inputs));
+        assert!(Nor.evaluate(&[false; 3]));
+    }
+
+    #[test]
+    fn half_adder() {
+        let mut circuit = Circuit::new();
+        let a = circuit.add_pin();
+        let b = circuit.add_pin();
+        let sum = circuit.add_pin();
+        let carry = circuit.add_pin();
+
+        let xor = circuit.add_gate(GateType::Xor);
+        let and = circuit.add_gate(GateType::And);
+        for gate in [xor, and] {
+            let gate = circuit.gate(gate).unwrap().clone();
+            circuit.connect(a, gate.inputs[0]).unwrap();
+            circuit.connect(b, gate.inputs[1]).unwrap();
+        }
+        circuit.connect(circuit.gate(xor).unwrap().outputs[0], sum).unwrap();
+        circuit.connect(circuit.gate(and).unwrap().outputs[0], carry).unwrap();
+
+        for (va, vb) in [(false, false), (false, true), (true, false), (true, true)] {
+            circuit.set(a, va);
+            circuit.set(b, vb);
+            circuit.run_until_stable(10).unwrap();
+
+            assert_eq!(circuit.get(sum), va ^ vb);
+            assert_eq!(circuit.get(carry), va & vb);
+        }
+    }
+
+    #[test]
+    fn wires_take_one_tick() {
+        let mut circuit = Circuit::new();
+        let a = circuit.add_pin();
+        let b = circuit.add_pin();
+        circuit.connect(a, b).unwrap();
+
+        circuit.set(a, true);
+        assert!(circuit.step());
+        assert!(circuit.get(b));
+        assert!(!circuit.step());
+    }
+
+    #[test]
+    fn clock_follows_its_duty_cycle() {
+        let mut circuit = Circuit::new();
+        let clock = circuit.add_gate(GateType::Clock(ClockConfig { period: 4, high: 1 }));
+        let output = circuit.gate(clock).unwrap().outputs[0];
+
+        let mut values = vec![];
+        for _ in 0..8 {
+            assert!(circuit.step());
+            values.push(circuit.get(output));
+        }
+        assert_eq!(values, [true, false, false, false, true, false, false, false]);
+        assert_eq!(circuit.run_until_stable(100), Err(Unstable));
+    }
+
+    #[test]
+    fn paused_clock_holds_its_value() {
+        let mut circuit = Circuit::new();
+        let clock = circuit.add_gate(GateType::Clock(ClockConfig::default()));
+        let output = circuit.gate(clock).unwrap().outputs[0];
+        circuit.set_state(clock, GateState { paused: true, ..Default::default() });
+
+        assert_eq!(circuit.run_until_stable(100), Ok(1));
+        assert!(circuit.get(output));
+    }
+
+    #[test]
+    fn inverter_loop_never_settles() {
+        let mut circuit = Circuit::new();
+        let not = circuit.add_gate(GateType::Not);
+        let not = circuit.gate(not).unwrap().clone();
+        circuit.connect(not.outputs[0], not.inputs[0]).unwrap();
+
+        // Left floating, the input makes the loop settle on an unknown value
+        assert_eq!(circuit.run_until_stable(100), Ok(1));
+        assert_eq!(circuit.signal(not.outputs[0]), Signal::unknown(1));
+
+        circuit.set(not.inputs[0], false);
+        assert_eq!(circuit.run_until_stable(100), Err(Unstable));
+        assert_eq!(circuit.combinational_cycles(), [vec![GateId(0)]]);
+    }
+
+    #[test]
+    fn finds_combinational_cycles() {
+        let mut circuit = Circuit::new();
+        let gates: Vec<_> = [GateType::Nor, GateType::Nor, GateType::DFlipFlop, GateType::Not]
+            .into_iter()
+            .map(|kind| {
+                let id = circuit.add_gate(kind);
+                circuit.gate(id).unwrap().clone()
+            })
+            .collect();
+        let [nor_a, nor_b, flip_flop, not] = &gates[..] else { unreachable!() };
+
+        // A latch made of two Nor gates, and a flip-flop feeding its own input through a Not gate
+        circuit.connect(nor_a.outputs[0], nor_b.inputs[0]).unwrap();
+        circuit.connect(nor_b.outputs[0], nor_a.inputs[1]).unwrap();
+        circuit.connect(flip_flop.outputs[0], not.inputs[0]).unwrap();
+        circuit.connect(not.outputs[0], flip_flop.inputs[0]).unwrap();
+
+        let mut cycles = circuit.combinational_cycles();
+        assert_eq!(cycles.len(), 1);
+        cycles[0].sort();
+        assert_eq!(cycles[0], [GateId(0), GateId(1)]);
+    }
+
+    #[test]
+    fn pins_float_once_their_last_wire_is_removed() {
+        let mut circuit = Circuit::new();
+        let (a, b, y) = (circuit.add_pin(), circuit.add_pin(), circuit.add_pin());
+        let (from_a, from_b) = (circuit.connect(a, y).unwrap(), circuit.connect(b, y).unwrap());
+        circuit.set(a, true);
+        circuit.set(b, true);
+        circuit.run_until_stable(10).unwrap();
+
+        circuit.remove_wire(from_a);
+        assert!(circuit.get(y));
+        circuit.remove_wire(from_b);
+        assert_eq!(circuit.signal(y), Signal::floating(1));
+        circuit.run_until_stable(10).unwrap();
+        assert_eq!(circuit.signal(y), Signal::floating(1));
+    }
+
+    #[test]
+    fn removing_a_gate_removes_its_wires() {
+        let mut circuit = Circuit::new();
+        let a = circuit.add_pin();
+        let not = circuit.add_gate(GateType::Not);
+        let input = circuit.gate(not).unwrap().inputs[0];
+        let wire = circuit.connect(a, input).unwrap();
+
+        circuit.remove_gate(not);
+        assert!(circuit.gate(not).is_none());
+        assert!(circuit.wire(wire).is_none());
+    }
+
+    #[test]
+    fn settling_leaves_clocks_alone() {
+        let mut circuit = Circuit::new();
+        let (output, inverted) = (circuit.add_pin(), circuit.add_pin());
+        let clock = circuit.add_gate(GateType::Clock(ClockConfig { period: 2, high: 1 }));
+        let not = circuit.add_gate(GateType::Not);
+        let [clock, not] = circuit.gates_of([clock, not]);
+        circuit.connect(clock.outputs[0], output).unwrap();
+        circuit.connect(output, not.inputs[0]).unwrap();
+        circuit.connect(not.outputs[0], inverted).unwrap();
+
+        assert!(circuit.settle(10).is_ok());
+        assert_eq!((circuit.get(output), circuit.get(inverted)), (true, false));
+        assert!(circuit.tick_clocks());
+        assert!(circuit.settle(10).is_ok());
+        assert_eq!((circuit.get(output), circuit.get(inverted)), (false, true));
+    }
+
+    fn clocked(kind: GateType, steps: &[&[bool]]) -> Vec<bool> {
+        let mut state = GateState::default();
+        steps
+            .iter()
+            .map(|inputs| {
+                let inputs: Vec<_> = inputs.iter().map(|&v| Signal::known(v as u64)).collect();
+                kind.update(&inputs, 1, &mut state)[0] == Signal::known(1)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sr_latch_holds_its_value() {
+        let q = clocked(GateType::SrLatch, &[&[true, false], &[false, false], &[false, true], &[false, false], &[true, true]]);
+        assert_eq!(q, [true, true, false, false, false]);
+    }
+
+    #[test]
+    fn flip_flops_trigger_on_rising_edges() {
+        use GateType::*;
+        // D, Clk
+        let q = clocked(DFlipFlop, &[&[true, false], &[true, true], &[false, true], &[false, false], &[false, true]]);
+        assert_eq!(q, [false, true, true, true, false]);
+
+        // J, K, Clk
+        let q = clocked(JkFlipFlop, &[&[true, false, true], &[true, true, false], &[true, true, true], &[false, false, false], &[false, true, true]]);
+        assert_eq!(q, [true, true, false, false, false]);
+
+        // T, Clk
+        let q = clocked(TFlipFlop, &[&[true, true], &[true, false], &[true, true], &[false, false], &[false, true]]);
+        assert_eq!(q, [true, true, false, false, false]);
+    }
+
+    #[test]
+    fn asynchronous_set_and_reset() {
+        // D, Clk, S, R
+        let q = clocked(GateType::DFlipFlop, &[&[false, false, true, false], &[false, false, false, false], &[true, true, false, true]]);
+        assert_eq!(q, [true, true, false]);
+
+        let mut state = GateState::default();
+        let inputs = [0, 0, 1].map(Signal::known);
+        assert_eq!(GateType::TFlipFlop.update(&inputs, 1, &mut state), [Signal::known(1), Signal::known(0)]);
+
+        // Without set and reset
+        let inputs = [Signal::known(1), Signal::known(1), Signal::floating(1), Signal::floating(1)];
+        assert_eq!(GateType::TFlipFlop.update(&inputs, 1, &mut state)[0], Signal::known(0));
+    }
+
+    #[test]
+    fn unknown_inputs_propagate_unless_they_dont_matter() {
+        use GateType::*;
+        let (x, z) = (Signal::unknown(1), Signal::floating(1));
+        let (low, high) = (Signal::known(0), Signal::known(1));
+        assert_eq!(And.evaluate_signals(&[x, low], 1), low);
+        assert_eq!(And.evaluate_signals(&[x, high], 1), x);
+        assert_eq!(Nor.evaluate_signals(&[z, high], 1), low);
+        assert_eq!(Or.evaluate_signals(&[z, low], 1), x);
+        assert_eq!(Xor.evaluate_signals(&[x, high], 1), x);
+        assert_eq!(Not.evaluate_signals(&[z], 1), x);
+
+        // Bit by bit across buses
+        let a = Signal { bits: 0b0010, z: 0b0100, x: 0b1000 };
+        assert_eq!(Nand.evaluate_signals(&[a, Signal::known(0b1111)], 4), Signal { bits: 0b0001, z: 0, x: 0b1100 });
+
+        // Sequential gates don't change state on unknown inputs
+        let mut state = GateState { q: true, ..Default::default() };
+        assert_eq!(DFlipFlop.update(&[low, x], 1, &mut state), [x, x]);
+        assert!(state.q);
+    }
+
+    #[test]
+    fn tri_state_buffers_share_a_bus() {
+        let mut circuit = Circuit::new();
+        let bus = circuit.add_bus(4);
+        let buffers: Vec<_> = [0x3, 0xc]
+            .into_iter()
+            .map(|value| {
+                let id = circuit.add_gate_with(GateType::TriState, 2, 4);
+                let gate = circuit.gate(id).unwrap().clone();
+                circuit.set_value(gate.inputs[0], value);
+                circuit.connect(gate.outputs[0], bus).unwrap();
+                gate
+            })
+            .collect();
+        let enable = |circuit: &mut Circuit, enabled: [bool; 2]| {
+            for (gate, enabled) in buffers.iter().zip(enabled) {
+                circuit.set(gate.inputs[1], enabled);
+            }
+            circuit.run_until_stable(10).unwrap();
+        };
+
+        enable(&mut circuit, [false, false]);
+        assert_eq!(circuit.signal(bus), Signal::floating(4));
+        assert_eq!(circuit.signal(bus).format(4), "ZZZZ");
+
+        enable(&mut circuit, [true, false]);
+        assert_eq!(circuit.signal(bus), Signal::known(0x3));
+        enable(&mut circuit, [false, true]);
+        assert_eq!(circuit.signal(bus), Signal::known(0xc));
+        assert!(circuit.conflicts().is_empty());
+
+        // Both drive every bit, to different levels
+        enable(&mut circuit, [true, true]);
+        assert_eq!(circuit.signal(bus), Signal::unknown(4));
+        assert_eq!(circuit.conflicts(), [bus]);
+    }
+
+    #[test]
+    fn flip_flop_divides_the_clock() {
+        let mut circuit = Circuit::new();
+        let clock = circuit.add_pin();
+        let t = circuit.add_gate(GateType::TFlipFlop);
+        let t = circuit.gate(t).unwrap().clone();
+        let high = circuit.add_pin();
+        circuit.set(high, true);
+        circuit.connect(high, t.inputs[0]).unwrap();
+        circuit.connect(clock, t.inputs[1]).unwrap();
+
+        let mut outputs = vec![];
+        for tick in 0..8 {
+            circuit.set(clock, tick % 2 == 1);
+            circuit.run_until_stable(10).unwrap();
+            outputs.push(circuit.get(t.outputs[0]));
+            assert_ne!(circuit.get(t.outputs[0]), circuit.get(t.outputs[1]));
+        }
+        assert_eq!(outputs, [false, true, true, false, false, true, true, false]);
+    }
+
+    #[test]
+    fn gates_apply_bitwise_across_buses() {
+        let mut circuit = Circuit::new();
+        let a = circuit.add_bus(8);
+        let b = circuit.add_bus(8);
+        let y = circuit.add_bus(8);
+        let nand = circuit.add_gate_with(GateType::Nand, 2, 8);
+        let nand = circuit.gate(nand).unwrap().clone();
+        circuit.connect(a, nand.inputs[0]).unwrap();
+        circuit.connect(b, nand.inputs[1]).unwrap();
+        circuit.connect(nand.outputs[0], y).unwrap();
+
+        circuit.set_value(a, 0x1f0);
+        circuit.set_value(b, 0x3c);
+        circuit.run_until_stable(10).unwrap();
+        assert_eq!(circuit.value(a), 0xf0);
+        assert_eq!(circuit.value(y), 0xcf);
+    }
+
+    #[test]
+    fn split_and_merge_buses() {
+        let mut circuit = Circuit::new();
+        let a = circuit.add_bus(4);
+        let y = circuit.add_bus(4);
+        let split = circuit.add_gate_with(GateType::Splitter, 1, 4);
+        let split = circuit.gate(split).unwrap().clone();
+        let merge = circuit.add_gate_with(GateType::Merger, 4, 4);
+        let merge = circuit.gate(merge).unwrap().clone();
+        circuit.connect(a, split.inputs[0]).unwrap();
+        // Reverses the bits
+        for bit in 0..4 {
+            circuit.connect(split.outputs[bit], merge.inputs[3 - bit]).unwrap();
+        }
+        circuit.connect(merge.outputs[0], y).unwrap();
+
+        circuit.set_value(a, 0b0011);
+        circuit.run_until_stable(10).unwrap();
+        assert_eq!(circuit.value(y), 0b1100);
+    }
+
+    #[test]
+    fn rejects_wires_between_different_widths() {
+        let mut circuit = Circuit::new();
+        let a = circuit.add_bus(8);
+        let b = circuit.add_pin();
+        assert_eq!(circuit.connect(a, b), Err(WidthMismatch { from: 8, to: 1 }));
+        assert_eq!(circuit.wires().count(), 0);
+    }
+
+    #[test]
+    fn resolves_multiple_drivers() {
+        let mut circuit = Circuit::new();
+        let (a, b, out) = (circuit.add_bus(4), circuit.add_bus(4), circuit.add_bus(4));
+        circuit.connect(a, out).unwrap();
+        circuit.connect(b, out).unwrap();
+        circuit.set_value(a, 0b1100);
+        circuit.set_value(b, 0b1010);
+
+        circuit.set_resolution(Resolution::WiredOr);
+        circuit.step();
+        assert_eq!(circuit.value(out), 0b1110);
+        assert!(circuit.conflicts().is_empty());
+
+        circuit.set_resolution(Resolution::WiredAnd);
+        circuit.step();
+        assert_eq!(circuit.value(out), 0b1000);
+
+        // Bits the drivers disagree on are unknown
+        circuit.set_resolution(Resolution::Exclusive);
+        circuit.step();
+        assert_eq!(circuit.signal(out), Signal { bits: 0b1000, z: 0, x: 0b0110 });
+        assert_eq!(circuit.conflicts(), [out]);
+
+        circuit.set_value(b, 0b1100);
+        circuit.step();
+        assert_eq!(circuit.value(out), 0b1100);
+        assert!(circuit.conflicts().is_empty());
+    }
+}
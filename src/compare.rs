@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use logic_sim::{
+    equivalence::{check, Verdict},
+    save::CircuitFile,
+};
+
+use crate::{
+    circuit::{Circuit, Interface},
+    component::{Library, Subcircuit},
+    file::FileStatus,
+};
+
+pub struct ComparePlugin;
+
+impl Plugin for ComparePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CheckEquivalence>().add_system(check_equivalence);
+    }
+}
+
+/// Compares two circuits written as `first == second`, each one the path of a saved circuit or the name of a
+/// component of the library
+pub struct CheckEquivalence(pub String);
+
+/// Component of the library with that name, or else the circuit saved at that path
+fn load_side(name: &str, library: &Library) -> Result<(Circuit, Interface), String> {
+    if let Some(idx) = library.0.iter().position(|component| component.name == name) {
+        let Subcircuit { circuit, interface } = Subcircuit::new(&library.0, idx);
+        return Ok((circuit, interface));
+    }
+
+    let mut file = CircuitFile::load(Path::new(name)).map_err(|e| format!("Could not open {name}: {e}"))?;
+    file.link_libraries().map_err(|e| format!("Could not open {name}: {e}"))?;
+    Ok(file.to_circuit())
+}
+
+fn check_equivalence(
+    mut events: EventReader<CheckEquivalence>,
+    mut status: ResMut<FileStatus>,
+    library: Res<Library>,
+) {
+    for CheckEquivalence(text) in events.iter() {
+        let Some((first, second)) = text.split_once("==") else {
+            status.0 = "Write the two circuits to compare as first.ron == second.ron".to_string();
+            continue;
+        };
+        let (first, second) = (first.trim(), second.trim());
+
+        let circuits = load_side(first, &library).and_then(|a| Ok((a, load_side(second, &library)?)));
+        let ((a, a_interface), (b, b_interface)) = match circuits {
+            Ok(circuits) => circuits,
+            Err(e) => {
+                status.0 = e;
+                continue;
+            }
+        };
+
+        status.0 = match check((&a, &a_interface), (&b, &b_interface), &mut rand::thread_rng()) {
+            Ok(Verdict::Equivalent { vectors, exhaustive: true }) => {
+                format!("{first} and {second} are equivalent, for all {vectors} combinations of inputs")
+            }
+            Ok(Verdict::Equivalent { vectors, exhaustive: false }) => {
+                format!("{first} and {second} agree on {vectors} random combinations of inputs")
+            }
+            Ok(Verdict::Differ(counterexample)) => format!("{first} and {second} differ for\n{counterexample}"),
+            Ok(Verdict::Inconclusive(unsettled)) => format!("Could not compare {first} and {second} for\n{unsettled}"),
+            Err(e) => format!("Could not compare {first} and {second}: {e}"),
+        };
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use logic_sim::save::{CircuitFile, ComponentSave, LibraryFile};
+
+use crate::{
+    circuit::{Circuit, GateType, Interface},
+    file::{CircuitSnapshot, FileStatus},
+    gate::Selection,
+};
+
+pub struct ComponentPlugin;
+
+impl Plugin for ComponentPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Library(vec![]))
+            .add_event::<MakeComponent>()
+            .add_event::<ImportLibrary>()
+            .add_event::<ExportLibrary>()
+            .add_system(make_component)
+            .add_system(import_library)
+            .add_system(export_library);
+    }
+}
+
+/// Custom components that can be placed in the circuit, indexed by [`GateType::Custom`](crate::circuit::GateType::Custom)
+#[derive(Resource)]
+pub struct Library(pub Vec<ComponentSave>);
+
+impl Library {
+    /// Name of a gate type, which is the name of the component for custom ones
+    pub fn name(&self, kind: GateType) -> &str {
+        match kind {
+            GateType::Custom(idx) => &self.0[idx].name,
+            kind => kind.as_str(),
+        }
+    }
+}
+
+/// Turns the selected gates, or the whole circuit if nothing is selected, into a component with the given name
+pub struct MakeComponent(pub String);
+
+/// Adds the components of a library file to the library
+pub struct ImportLibrary(pub PathBuf);
+
+/// Writes the components made in this circuit to a library file, and uses them from there from now on
+pub struct ExportLibrary(pub PathBuf);
+
+/// The inlined definition of a placed custom component, settled whenever its inputs change or its clocks tick
+#[derive(Component)]
+pub struct Subcircuit {
+    pub circuit: Circuit,
+    pub interface: Interface,
+}
+
+impl Subcircuit {
+    /// Builds the `idx`-th component of the library, along with the components it uses
+    pub fn new(library: &[ComponentSave], idx: usize) -> Self {
+        // Components only use the ones defined before them
+        let file = CircuitFile { components: library[..idx].to_vec(), ..library[idx].circuit.clone() };
+        let (circuit, interface) = file.to_circuit();
+        Self { circuit, interface }
+    }
+
+    /// Widths of the inputs and outputs
+    pub fn pin_widths(&self) -> (Vec<u8>, Vec<u8>) {
+        let widths = |pins: &[(String, _)]| pins.iter().map(|&(_, pin)| self.circuit.width(pin)).collect();
+        (widths(&self.interface.inputs), widths(&self.interface.outputs))
+    }
+
+    /// Labels of the inputs and outputs, from the side panels of the definition
+    pub fn pin_names(&self) -> (Vec<String>, Vec<String>) {
+        let names = |pins: &[(String, _)]| pins.iter().map(|(name, _)| name.clone()).collect();
+        (names(&self.interface.inputs), names(&self.interface.outputs))
+    }
+}
+
+fn make_component(
+    mut events: EventReader<MakeComponent>,
+    mut library: ResMut<Library>,
+    mut status: ResMut<FileStatus>,
+    mut selection: ResMut<Selection>,
+    snapshot: CircuitSnapshot,
+) {
+    for MakeComponent(name) in events.iter() {
+        if library.0.iter().any(|component| &component.name == name) {
+            status.0 = format!("A component is already named {name}");
+            continue;
+        }
+
+        let mut circuit = match selection.0.is_empty() {
+            true => snapshot.to_file(),
+            false => snapshot.selection_to_file(&selection.0).0,
+        };
+        // Definitions share the components of the file
+        circuit.components.clear();
+
+        let (inputs, outputs) = (circuit.inputs.len(), circuit.outputs.len());
+        library.0.push(ComponentSave { name: name.clone(), library: None, circuit });
+        selection.0.clear();
+        status.0 = format!("Made component {name} with {inputs} inputs and {outputs} outputs");
+    }
+}
+
+fn import_library(mut events: EventReader<ImportLibrary>, mut library: ResMut<Library>, mut status: ResMut<FileStatus>) {
+    for ImportLibrary(path) in events.iter() {
+        let file = match LibraryFile::load(path) {
+            Ok(file) => file,
+            Err(e) => {
+                status.0 = format!("Could not import {}: {e}", path.display());
+                continue;
+            }
+        };
+
+        let skipped = file.import(path, &mut library.0);
+        status.0 = match skipped.is_empty() {
+            true => format!("Imported {}", path.display()),
+            false => format!("Imported {}, except {} whose names are taken", path.display(), skipped.join(", ")),
+        };
+    }
+}
+
+fn export_library(mut events: EventReader<ExportLibrary>, mut library: ResMut<Library>, mut status: ResMut<FileStatus>) {
+    for ExportLibrary(path) in events.iter() {
+        let file = match LibraryFile::from_components(&library.0, path) {
+            Ok(file) => file,
+            Err(name) => {
+                status.0 = format!("Could not export {name}, which uses components from another library");
+                continue;
+            }
+        };
+
+        if let Err(e) = file.save(path) {
+            status.0 = format!("Could not export to {}: {e}", path.display());
+            continue;
+        }
+
+        for component in library.0.iter_mut().filter(|component| component.library.is_none()) {
+            component.library = Some(path.clone());
+        }
+        status.0 = format!("Exported {} components to {}", file.components.len(), path.display());
+    }
+}
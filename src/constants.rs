@@ -1,10 +1,19 @@
 use bevy::prelude::*;
+use crate::circuit::Signal;
 
 pub struct Colors;
 
 impl Colors {
     pub const ON: Color = Color::rgb(0.9, 0.3, 0.3);
     pub const OFF: Color = Color::DARK_GRAY;
+    /// Gates and edges flagged by the diagnostics
+    pub const WARNING: Color = Color::rgb(0.95, 0.7, 0.1);
+    /// Nodes whose drivers disagree
+    pub const CONFLICT: Color = Color::rgb(0.7, 0.3, 0.9);
+    /// Signals with floating bits
+    pub const HIGH_Z: Color = Color::rgb(0.3, 0.5, 0.9);
+    /// Signals with unknown bits
+    pub const UNKNOWN: Color = Color::rgb(0.3, 0.8, 0.5);
 
     pub const BG: Color = Color::rgb(0.4, 0.4, 0.4);
     pub const UI_BG: Color = Color::rgb(0.3, 0.3, 0.3);
@@ -16,6 +25,15 @@ impl Colors {
     pub fn highlighted(v: bool) -> Color {
         Self::value(v) + Color::WHITE*0.1
     }
+
+    /// Unknown bits show over floating ones, which show over the value of the others
+    pub fn signal(s: Signal) -> Color {
+        if s.x != 0 { Self::UNKNOWN } else if s.z != 0 { Self::HIGH_Z } else { Self::value(s.is_high()) }
+    }
+
+    pub fn highlighted_signal(s: Signal) -> Color {
+        Self::signal(s) + Color::WHITE*0.1
+    }
 }
 
 pub const RADIUS: f32 = 15.0;
@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    circuit::find_cycles,
+    component::{Library, Subcircuit},
+    constants::Colors,
+    edge::Edge,
+    gate::Gate,
+    node::Node,
+    ui::{button_style, text_builder},
+};
+
+pub struct DiagnosticsPlugin;
+
+impl Plugin for DiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Diagnostics::default())
+            // Like the simulation, looks at the gates and edges spawned during the frame
+            .add_system_to_stage(CoreStage::PostUpdate, find_loops)
+            .add_startup_system(create_diagnostics_ui)
+            .add_system(interact_diagnostics_ui)
+            .add_system(show_diagnostics);
+    }
+}
+
+const SETTLE_LIMITS: std::ops::RangeInclusive<u32> = 4..=1024;
+
+/// Problems found in the circuit, shown in the warning colour and listed in the diagnostics panel
+#[derive(Resource)]
+pub struct Diagnostics {
+    /// How many times a node can change because of a single stimulus, like an input toggled by hand or a clock tick,
+    /// before it is reported as oscillating
+    pub settle_limit: u32,
+    /// Gates of each loop that doesn't go through a sequential gate
+    pub loops: Vec<Vec<Entity>>,
+    /// Edges between gates of the same loop
+    pub loop_edges: HashSet<Entity>,
+    /// Nodes that kept changing without settling, filled in by the simulation
+    pub oscillating: HashSet<Entity>,
+    /// Nodes whose drivers disagree, filled in by the simulation
+    pub conflicts: HashSet<Entity>,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self {
+            settle_limit: 64,
+            loops: vec![],
+            loop_edges: HashSet::new(),
+            oscillating: HashSet::new(),
+            conflicts: HashSet::new(),
+        }
+    }
+}
+
+impl Diagnostics {
+    pub fn flags_gate(&self, entity: Entity, gate: &Gate) -> bool {
+        self.loops.iter().flatten().any(|&g| g == entity)
+            || gate.outputs.iter().any(|output| self.oscillating.contains(output))
+    }
+
+    pub fn flags_edge(&self, entity: Entity, from: Entity) -> bool {
+        self.loop_edges.contains(&entity) || self.oscillating.contains(&from)
+    }
+}
+
+/// Finds the loops of gates that feed each other without a flip-flop or latch to hold their value. Custom components
+/// count as combinational unless they hold one of those.
+#[allow(clippy::type_complexity)]
+fn find_loops(
+    mut diagnostics: ResMut<Diagnostics>,
+    gates: Query<(Entity, &Gate, Option<&Subcircuit>)>,
+    edges: Query<(Entity, &Edge)>,
+    parents: Query<&Parent, With<Node>>,
+    added: Query<(), Or<(Added<Gate>, Added<Edge>)>>,
+    removed_gates: RemovedComponents<Gate>,
+    removed_edges: RemovedComponents<Edge>,
+) {
+    if added.is_empty() && removed_gates.iter().next().is_none() && removed_edges.iter().next().is_none() {
+        return;
+    }
+
+    let combinational = |gate: Entity| {
+        gates.get(gate).is_ok_and(|(_, gate, subcircuit)| match subcircuit {
+            Some(subcircuit) => subcircuit.circuit.gates().all(|(_, gate)| !gate.kind.is_sequential()),
+            None => !gate.kind.is_sequential(),
+        })
+    };
+    // Pins of gates are their children
+    let gate_of = |node: Entity| Some(parents.get(node).ok()?.get()).filter(|&gate| combinational(gate));
+
+    let links: Vec<_> =
+        edges.iter().filter_map(|(edge, &Edge { from, to })| Some((edge, gate_of(from)?, gate_of(to)?))).collect();
+    let nodes: Vec<_> = gates.iter().map(|(gate, ..)| gate).filter(|&gate| combinational(gate)).collect();
+    // Built once, so the search doesn't go through every link for each gate
+    let mut successors: HashMap<_, Vec<_>> = HashMap::new();
+    for &(_, from, to) in &links {
+        successors.entry(from).or_default().push(to);
+    }
+    let loops = find_cycles(&nodes, |gate| successors.get(&gate).cloned().unwrap_or_default());
+
+    let loop_of: HashMap<_, _> =
+        loops.iter().enumerate().flat_map(|(idx, gates)| gates.iter().map(move |&gate| (gate, idx))).collect();
+    diagnostics.loop_edges = links
+        .iter()
+        .filter(|(_, from, to)| loop_of.contains_key(from) && loop_of.get(from) == loop_of.get(to))
+        .map(|&(edge, ..)| edge)
+        .collect();
+    diagnostics.loops = loops;
+}
+
+/// Problems listed in the panel
+#[derive(Component)]
+struct DiagnosticsList;
+
+#[derive(Component)]
+struct SettleLimitText;
+
+/// Halves or doubles the settle limit
+#[derive(Component)]
+struct SettleLimitButton(bool);
+
+fn create_diagnostics_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(75.0),
+                    top: Val::Px(60.0),
+                    ..default()
+                },
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            background_color: Colors::UI_BG.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            c.spawn(NodeBundle { style: Style { align_items: AlignItems::Center, ..default() }, ..default() })
+                .with_children(|c| {
+                    for (more, label) in [(false, "-"), (true, "+")] {
+                        if more {
+                            c.spawn((text_builder("", &asset_server), SettleLimitText));
+                        }
+                        c.spawn((
+                            ButtonBundle {
+                                style: button_style(),
+                                background_color: Colors::OFF.into(),
+                                ..default()
+                            },
+                            SettleLimitButton(more),
+                        ))
+                        .with_children(|c| {
+                            c.spawn(text_builder(label, &asset_server));
+                        });
+                    }
+                });
+
+            c.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::FlexEnd,
+                        padding: UiRect::bottom(Val::Px(10.0)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                DiagnosticsList,
+            ));
+        });
+}
+
+fn interact_diagnostics_ui(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &SettleLimitButton), Changed<Interaction>>,
+    mut diagnostics: ResMut<Diagnostics>,
+) {
+    for (interaction, mut color, &SettleLimitButton(more)) in &mut query {
+        match *interaction {
+            Interaction::None => *color = Colors::OFF.into(),
+            Interaction::Hovered => *color = Colors::highlighted(false).into(),
+            Interaction::Clicked => {
+                *color = Colors::ON.into();
+                let limit = if more { diagnostics.settle_limit * 2 } else { diagnostics.settle_limit / 2 };
+                diagnostics.settle_limit = limit.clamp(*SETTLE_LIMITS.start(), *SETTLE_LIMITS.end());
+            }
+        }
+    }
+}
+
+/// Names of the gates listed in the diagnostics panel
+#[derive(SystemParam)]
+struct GateNames<'w, 's> {
+    library: Res<'w, Library>,
+    gates: Query<'w, 's, &'static Gate>,
+    parents: Query<'w, 's, &'static Parent, With<Node>>,
+}
+
+impl<'w, 's> GateNames<'w, 's> {
+    fn of(&self, entities: &[Entity]) -> String {
+        let names: Vec<_> = self.gates.iter_many(entities).map(|gate| self.library.name(gate.kind)).collect();
+        names.join(", ")
+    }
+
+    /// Gates the nodes are pins of, once each
+    fn owners(&self, nodes: impl Iterator<Item = Entity>) -> Vec<Entity> {
+        let mut gates: Vec<_> = nodes.filter_map(|node| Some(self.parents.get(node).ok()?.get())).collect();
+        gates.sort();
+        gates.dedup();
+        gates
+    }
+}
+
+/// Lists the loops, the oscillating gates and the conflicts, one per line
+fn show_diagnostics(
+    mut commands: Commands,
+    diagnostics: Res<Diagnostics>,
+    names: GateNames,
+    list: Query<Entity, With<DiagnosticsList>>,
+    mut limit: Query<&mut Text, With<SettleLimitText>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !diagnostics.is_changed() {
+        return;
+    }
+    limit.single_mut().sections[0].value = format!("Settle within {} changes", diagnostics.settle_limit);
+
+    let mut lines: Vec<_> =
+        diagnostics.loops.iter().map(|gates| format!("Loop without memory: {}", names.of(gates))).collect();
+
+    // Only gate outputs are worth listing, the nodes they feed follow them
+    let oscillating = names.owners(diagnostics.oscillating.iter().copied());
+    if !oscillating.is_empty() {
+        lines.push(format!("Oscillating: {}", names.of(&oscillating)));
+    }
+    if !diagnostics.conflicts.is_empty() {
+        lines.push(format!("Conflicting drivers on {} nodes", diagnostics.conflicts.len()));
+    }
+
+    let list = list.single();
+    commands.entity(list).despawn_descendants();
+    commands.entity(list).with_children(|c| {
+        for line in lines {
+            c.spawn(text_builder(&line, &asset_server));
+        }
+    });
+}
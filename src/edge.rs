@@ -8,9 +8,14 @@ use bevy_prototype_lyon::{
 };
 
 use crate::{
+    circuit::Signal,
     cursor::Cursor,
-    node::{HoveredNode, Node},
-    constants::{Colors, Depth}
+    history::{Edit, EdgeRecord, History},
+    file::FileStatus,
+    node::{HoveredNode, Node, PinDirection},
+    constants::{Colors, Depth, RADIUS},
+    diagnostics::Diagnostics,
+    simulation::EventQueue,
 };
 
 pub struct EdgePlugin;
@@ -19,40 +24,55 @@ impl Plugin for EdgePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(SelectedNode(None))
             .insert_non_send_resource(HoveredEdge(None))
-            .add_system(propagate)
             .add_system(move_edge)
             .add_system(set_edge_color)
             .add_system(hover_edge)
             .add_system(create_edges)
             .add_system(delete_edges)
-            .add_system(cleanup_edges);
+            .add_system(cleanup_edges)
+            .add_startup_system(create_bus_tooltip)
+            .add_system(show_bus_value);
     }
 }
 
+/// Thickness of edges carrying a single bit
+const WIRE_THICKNESS: f32 = 5.0;
+const BUS_THICKNESS: f32 = 10.0;
+
 #[derive(Bundle)]
 pub struct EdgeBundle {
     pub edge: Edge,
     shape: ShapeBundle,
-    timer: EdgeTimer
+    signal: EdgeSignal,
 }
 
 impl EdgeBundle {
     pub fn new(a: Entity, b: Entity) -> Self {
-        let mut timer = EdgeTimer(Timer::from_seconds(0.1, TimerMode::Once));
-        timer.0.set_elapsed(timer.0.duration());
-
         // GeometryBuilder::build_as(Path, mode, transform)
 
         Self {
             edge: Edge { from: a, to: b },
             shape: GeometryBuilder::build_as(
                 &Line(Vec2::ZERO, Vec2::ZERO),
-                DrawMode::Stroke(StrokeMode::new(Colors::OFF, 5.0)),
+                DrawMode::Stroke(StrokeMode::new(Colors::OFF, WIRE_THICKNESS)),
                 Transform::from_xyz(0.0, 0.0, Depth::EDGE),
             ),
-            timer
+            signal: EdgeSignal {
+                delay: None,
+                value: Signal::default(),
+                previous: Signal::default(),
+                sent: Duration::ZERO,
+                arrival: Duration::ZERO,
+                arrived: None,
+            },
         }
     }
+
+    /// Sets the delay of the edge in ticks, or leaves it to the default one
+    pub fn delay(mut self, delay: Option<u32>) -> Self {
+        self.signal.delay = delay;
+        self
+    }
 }
 
 #[derive(Component)]
@@ -61,34 +81,34 @@ pub struct Edge {
     pub to: Entity,
 }
 
-/// Determines how much a signal progressed through an edge
+/// Last value sent through an edge, and when it was sent and arrives, in simulated time
 #[derive(Component)]
-pub struct EdgeTimer(pub Timer);
-
-fn propagate(mut query: Query<(&Edge, &mut EdgeTimer)>, mut nodes: Query<&mut Node>, time: Res<Time>) {
-    for ( &Edge { from, to }, mut timer ) in &mut query {
-        let Ok([a, mut b]) = nodes.get_many_mut([ from, to ]) else { continue };
-
-        if timer.0.finished() && b.0 != a.0 {
-            timer.0.reset();
-        }
-        else if !timer.0.finished() {
-            timer.0.tick(time.delta());
-            if b.0 == a.0 {
-                let d = timer.0.duration();
-                timer.0.set_elapsed(d);
-            }
-        }
+pub struct EdgeSignal {
+    /// Delay in ticks, if it isn't the default one
+    pub delay: Option<u32>,
+    pub value: Signal,
+    /// Value sent before the last one, still shown ahead of it
+    pub previous: Signal,
+    pub sent: Duration,
+    pub arrival: Duration,
+    /// Last value that reached the end of the edge
+    pub arrived: Option<Signal>,
+}
 
-        if timer.0.just_finished() {
-            b.0 = a.0;
+impl EdgeSignal {
+    /// How far the last value went through the edge, from 0 to 1
+    fn progress(&self, now: Duration) -> f32 {
+        let duration = self.arrival.saturating_sub(self.sent);
+        if duration.is_zero() {
+            return 1.0;
         }
+        (now.saturating_sub(self.sent).as_secs_f32() / duration.as_secs_f32()).min(1.0)
     }
 }
 
 // Holds a reference to the edge the mouse is currently hovering over
 #[derive(Resource)]
-struct HoveredEdge(Option<Entity>);
+pub struct HoveredEdge(pub Option<Entity>);
 
 fn hover_edge(
     edges: Query<(Entity, &Edge)>,
@@ -138,7 +158,7 @@ fn move_edge(
     nodes: Query<(&GlobalTransform, ChangeTrackers<GlobalTransform>), With<Node>>,
 ) {
     for (&Edge { from, to }, mut path, edge_change) in &mut edges {
-        let Ok([( a, a_change ), (b, b_change)]) = nodes.get_many([from, to]) else { return };
+        let Ok([( a, a_change ), (b, b_change)]) = nodes.get_many([from, to]) else { continue };
 
         if a_change.is_changed() || b_change.is_changed() || edge_change.is_changed() {
             let line = Line(a.translation().truncate(), b.translation().truncate());
@@ -148,23 +168,33 @@ fn move_edge(
 }
 
 fn set_edge_color(
-    mut edges: Query<(Entity, &Edge, &EdgeTimer, &mut DrawMode)>,
+    mut edges: Query<(Entity, &Edge, &EdgeSignal, &mut DrawMode)>,
     nodes: Query<&Node>,
     hovered: Res<HoveredEdge>,
+    queue: Res<EventQueue>,
+    diagnostics: Res<Diagnostics>,
 ) {
-    for (edge, &Edge { from, to }, timer, mut draw_mode) in &mut edges {
-        let Ok([ from, to]) = nodes.get_many([from, to]) else { return };
+    for (edge, &Edge { from: source, to }, signal, mut draw_mode) in &mut edges {
+        let Ok([ from, _ ]) = nodes.get_many([source, to]) else { continue };
 
         let DrawMode::Stroke(ref mut stroke_mode) = *draw_mode else { return };
 
         let func = if Some(edge) == hovered.0 {
-            Colors::highlighted
+            Colors::highlighted_signal
         } else {
-            Colors::value
+            Colors::signal
         };
 
-        // Linear interpolation based on timer
-        stroke_mode.color = func(!from.0) * timer.0.percent_left() + func(from.0) * timer.0.percent();
+        // Linear interpolation following the signal through the edge
+        let progress = signal.progress(queue.now());
+        stroke_mode.color = func(signal.previous) * (1.0 - progress) + func(signal.value) * progress;
+        if diagnostics.flags_edge(edge, source) {
+            stroke_mode.color = match Some(edge) == hovered.0 {
+                true => Colors::WARNING + Color::WHITE * 0.1,
+                false => Colors::WARNING,
+            };
+        }
+        stroke_mode.options.line_width = if from.is_bus() { BUS_THICKNESS } else { WIRE_THICKNESS };
     }
 }
 
@@ -172,33 +202,59 @@ fn set_edge_color(
 #[derive(Resource)]
 struct SelectedNode(Option<Entity>);
 
+/// Connects the node the right mouse button was pressed on to the one it is released on.
+/// Edges run from the driver to the sink whichever way they are drawn.
 fn create_edges(
     mut commands: Commands,
     mut selected_node: ResMut<SelectedNode>,
+    mut history: ResMut<History>,
+    mut status: ResMut<FileStatus>,
+    nodes: Query<(&Node, &PinDirection)>,
     hovered: Res<HoveredNode>,
     mouse_input: Res<Input<MouseButton>>,
 ) {
     if mouse_input.just_pressed(MouseButton::Right) {
         selected_node.0 = hovered.0;
     } else if mouse_input.just_released(MouseButton::Right) {
-        let Some(selected) = selected_node.0 else { return };
+        let Some(selected) = selected_node.0.take() else { return };
+        let Some(hovered) = hovered.0.filter(|&hovered| hovered != selected) else { return };
+        let Ok([(a, &a_direction), (b, &b_direction)]) = nodes.get_many([selected, hovered]) else { return };
 
-        if let Some(hovered) = hovered.0 {
-            commands.spawn(EdgeBundle::new(selected, hovered));
+        if a.width != b.width {
+            status.0 = format!("Can't connect pins of {} and {} bits", a.width, b.width);
+            return;
+        }
+        let (from, to) = match (a_direction, b_direction) {
+            (PinDirection::Driver, PinDirection::Sink) => (selected, hovered),
+            (PinDirection::Sink, PinDirection::Driver) => (hovered, selected),
+            (PinDirection::Driver, PinDirection::Driver) => {
+                status.0 = "Can't connect two driving pins together".into();
+                return;
+            }
+            (PinDirection::Sink, PinDirection::Sink) => {
+                status.0 = "Can't connect two receiving pins together".into();
+                return;
+            }
         };
-        selected_node.0 = None;
+
+        let edge = commands.spawn(EdgeBundle::new(from, to)).id();
+        history.push(Edit::AddEdge(EdgeRecord { edge, from, to, delay: None }));
     }
 }
 
 fn delete_edges(
     mut commands: Commands,
+    mut history: ResMut<History>,
+    edges: Query<(&Edge, &EdgeSignal)>,
     mouse_input: Res<Input<MouseButton>>,
     hovered_edge: Res<HoveredEdge>,
 ) {
     if mouse_input.just_released(MouseButton::Right) {
         let Some(hovered) = hovered_edge.0 else { return };
+        let Ok((&Edge { from, to }, signal)) = edges.get(hovered) else { return };
 
         commands.entity(hovered).despawn();
+        history.push(Edit::RemoveEdge(EdgeRecord { edge: hovered, from, to, delay: signal.delay }));
     }
 }
 
@@ -214,3 +270,49 @@ fn cleanup_edges(
         }
     }
 }
+
+/// Shows the value of the bus under the mouse
+#[derive(Component)]
+struct BusTooltip;
+
+fn create_bus_tooltip(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraCode.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_alignment(TextAlignment {
+                horizontal: HorizontalAlign::Left,
+                vertical: VerticalAlign::Bottom,
+            }),
+            ..default()
+        },
+        BusTooltip,
+    ));
+}
+
+fn show_bus_value(
+    mut tooltip: Query<(&mut Text, &mut Transform, &mut Visibility), With<BusTooltip>>,
+    edges: Query<&Edge>,
+    nodes: Query<&Node>,
+    hovered_edge: NonSend<HoveredEdge>,
+    hovered_node: Res<HoveredNode>,
+    cursor: Res<Cursor>,
+) {
+    let (mut text, mut transform, mut visibility) = tooltip.single_mut();
+
+    let hovered = hovered_node.0.or_else(|| edges.get(hovered_edge.0?).ok().map(|edge| edge.from));
+    let Some(node) = hovered.and_then(|node| nodes.get(node).ok()).filter(|node| node.is_bus()) else {
+        visibility.is_visible = false;
+        return;
+    };
+
+    text.sections[0].value = node.value.format(node.width);
+    transform.translation = (cursor.0 + Vec2::splat(RADIUS)).extend(Depth::UI);
+    visibility.is_visible = true;
+}
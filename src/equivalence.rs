@@ -0,0 +1,280 @@
+//! Checks that two circuits give the same outputs for the same inputs, trying every combination of the inputs when
+//! there are few of them and random ones otherwise.
+//!
+//! Inputs and outputs are matched by name, so the side panels of the two circuits can be in different orders.
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::{
+    circuit::{mask, Circuit, GateType, Interface, PinId, Signal},
+    truth_table::{settle, split_combination, Column},
+};
+
+/// Number of input bits up to which every combination is tried
+pub const EXHAUSTIVE_BITS: u32 = 16;
+
+/// Number of random combinations tried when the inputs have more bits than that
+pub const RANDOM_VECTORS: u64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Both circuits gave the same outputs for each of the `vectors` combinations tried, which are all of them when
+    /// `exhaustive`
+    Equivalent { vectors: u64, exhaustive: bool },
+    Differ(Counterexample),
+    /// A circuit never settled, so nothing can be said about the combinations left
+    Inconclusive(Unsettled),
+}
+
+/// First combination of inputs for which the circuits disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub inputs: Vec<(Column, u64)>,
+    /// Outputs that differ, with their value in the first and the second circuit
+    pub outputs: Vec<(Column, Signal, Signal)>,
+}
+
+/// First combination of inputs for which a circuit kept changing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsettled {
+    pub inputs: Vec<(Column, u64)>,
+    /// Whether the first and the second circuit settled
+    pub settled: [bool; 2],
+}
+
+/// Why two circuits can't be compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EquivalenceError {
+    /// An input or output of one circuit has no pin of the same name in the other one
+    Missing { name: String, in_first: bool },
+    /// Pins of the same name have different widths
+    Width { name: String, first: u8, second: u8 },
+    /// One of the circuits keeps a state, so its outputs don't only depend on its inputs
+    Sequential(GateType),
+}
+
+impl fmt::Display for EquivalenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquivalenceError::Missing { name, in_first } => {
+                write!(f, "{name} is only in the {} circuit", if *in_first { "first" } else { "second" })
+            }
+            EquivalenceError::Width { name, first, second } => {
+                write!(f, "{name} has {first} bits in the first circuit and {second} in the second")
+            }
+            EquivalenceError::Sequential(kind) => write!(f, "the {} keeps a state", kind.as_str()),
+        }
+    }
+}
+
+impl std::error::Error for EquivalenceError {}
+
+/// Pins of the second circuit in the order of the first one, along with their names and widths
+fn match_pins(
+    first: (&Circuit, &[(String, PinId)]),
+    second: (&Circuit, &[(String, PinId)]),
+) -> Result<Vec<(Column, PinId, PinId)>, EquivalenceError> {
+    let missing = |name: &String, in_first| EquivalenceError::Missing { name: name.clone(), in_first };
+    if let Some((name, _)) = second.1.iter().find(|(name, _)| first.1.iter().all(|(other, _)| other != name)) {
+        return Err(missing(name, false));
+    }
+
+    first
+        .1
+        .iter()
+        .map(|(name, pin)| {
+            let &(_, other) = second.1.iter().find(|(other, _)| other == name).ok_or_else(|| missing(name, true))?;
+            let (width, other_width) = (first.0.width(*pin), second.0.width(other));
+            if width != other_width {
+                return Err(EquivalenceError::Width { name: name.clone(), first: width, second: other_width });
+            }
+            Ok((Column { name: name.clone(), width }, *pin, other))
+        })
+        .collect()
+}
+
+/// Compares the outputs of two circuits with the same inputs and outputs, for every combination of the inputs if
+/// they have up to [`EXHAUSTIVE_BITS`] bits, and for [`RANDOM_VECTORS`] combinations drawn from `rng` otherwise.
+/// The comparison stops at the first combination for which either circuit doesn't settle.
+pub fn check(
+    first: (&Circuit, &Interface),
+    second: (&Circuit, &Interface),
+    rng: &mut impl Rng,
+) -> Result<Verdict, EquivalenceError> {
+    for (circuit, _) in [first, second] {
+        if let Some((_, gate)) = circuit.gates().find(|(_, gate)| gate.kind.is_sequential()) {
+            return Err(EquivalenceError::Sequential(gate.kind));
+        }
+    }
+    let inputs = match_pins((first.0, &first.1.inputs), (second.0, &second.1.inputs))?;
+    let outputs = match_pins((first.0, &first.1.outputs), (second.0, &second.1.outputs))?;
+
+    let widths: Vec<_> = inputs.iter().map(|(column, _, _)| column.width).collect();
+    let bits: u32 = widths.iter().map(|&width| width as u32).sum();
+    let exhaustive = bits <= EXHAUSTIVE_BITS;
+    let vectors = if exhaustive { 1 << bits } else { RANDOM_VECTORS };
+
+    let first_outputs: Vec<_> = outputs.iter().map(|&(_, pin, _)| pin).collect();
+    let second_outputs: Vec<_> = outputs.iter().map(|&(_, _, pin)| pin).collect();
+    for vector in 0..vectors {
+        let values = match exhaustive {
+            true => split_combination(vector, &widths),
+            false => widths.iter().map(|&width| mask(rng.gen(), width)).collect(),
+        };
+
+        let pins = |side: fn(&(Column, PinId, PinId)) -> PinId| inputs.iter().map(side).zip(values.iter().copied());
+        let a = settle(first.0, pins(|&(_, pin, _)| pin), &first_outputs);
+        let b = settle(second.0, pins(|&(_, _, pin)| pin), &second_outputs);
+        let inputs = || inputs.iter().map(|(column, _, _)| column.clone()).zip(values.iter().copied()).collect();
+        match (a, b) {
+            (Ok(a), Ok(b)) if a != b => {
+                let differing = outputs.iter().zip(a.into_iter().zip(b)).filter(|(_, (a, b))| a != b);
+                return Ok(Verdict::Differ(Counterexample {
+                    inputs: inputs(),
+                    outputs: differing.map(|((column, _, _), (a, b))| (column.clone(), a, b)).collect(),
+                }));
+            }
+            (Ok(_), Ok(_)) => {}
+            (a, b) => return Ok(Verdict::Inconclusive(Unsettled { inputs: inputs(), settled: [a.is_ok(), b.is_ok()] })),
+        }
+    }
+
+    Ok(Verdict::Equivalent { vectors, exhaustive })
+}
+
+/// Writes the inputs as a table of one row under their names
+fn write_inputs(f: &mut fmt::Formatter<'_>, inputs: &[(Column, u64)]) -> fmt::Result {
+    let values: Vec<_> = inputs.iter().map(|(column, value)| Signal::known(*value).format(column.width)).collect();
+    let widths: Vec<_> =
+        inputs.iter().zip(&values).map(|((column, _), value)| column.name.chars().count().max(value.len())).collect();
+
+    let line = |fields: Vec<&str>| {
+        let fields: Vec<_> = fields.iter().zip(&widths).map(|(field, &width)| format!("{field:>width$}")).collect();
+        fields.join(" ")
+    };
+    writeln!(f, "{}", line(inputs.iter().map(|(column, _)| column.name.as_str()).collect()))?;
+    write!(f, "{}", line(values.iter().map(String::as_str).collect()))
+}
+
+/// Writes the inputs, then the outputs that differ
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_inputs(f, &self.inputs)?;
+        for (column, a, b) in &self.outputs {
+            let (a, b) = (a.format(column.width), b.format(column.width));
+            write!(f, "\n{} is {a} in the first circuit and {b} in the second", column.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the inputs, then which circuits didn't settle
+impl fmt::Display for Unsettled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_inputs(f, &self.inputs)?;
+        match self.settled {
+            [false, false] => write!(f, "\nNeither circuit settles"),
+            [false, _] => write!(f, "\nThe first circuit doesn't settle"),
+            _ => write!(f, "\nThe second circuit doesn't settle"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// Circuit computing the given gate of two inputs, named A and B, into an output named Y
+    fn gate(kind: GateType, width: u8) -> (Circuit, Interface) {
+        let mut circuit = Circuit::new();
+        let (a, b, y) = (circuit.add_bus(width), circuit.add_bus(width), circuit.add_bus(width));
+        let gate = circuit.add_gate_with(kind, 2, width);
+        let gate = circuit.gate(gate).unwrap().clone();
+        circuit.connect(a, gate.inputs[0]).unwrap();
+        circuit.connect(b, gate.inputs[1]).unwrap();
+        circuit.connect(gate.outputs[0], y).unwrap();
+
+        (circuit, Interface::named(&[("A", a), ("B", b)], &[("Y", y)]))
+    }
+
+    /// Nand built from an And and a Not, with its inputs listed in the other order
+    fn nand_of_and() -> (Circuit, Interface) {
+        let (mut circuit, mut interface) = gate(GateType::And, 1);
+        let (and_output, y) = (interface.outputs[0].1, circuit.add_pin());
+        let not = circuit.add_gate(GateType::Not);
+        let not = circuit.gate(not).unwrap().clone();
+        circuit.connect(and_output, not.inputs[0]).unwrap();
+        circuit.connect(not.outputs[0], y).unwrap();
+        interface.outputs[0].1 = y;
+        interface.inputs.reverse();
+        (circuit, interface)
+    }
+
+    fn check(first: &(Circuit, Interface), second: &(Circuit, Interface)) -> Result<Verdict, EquivalenceError> {
+        super::check((&first.0, &first.1), (&second.0, &second.1), &mut StdRng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn proves_equivalence_exhaustively() {
+        let verdict = check(&gate(GateType::Nand, 1), &nand_of_and());
+        assert_eq!(verdict, Ok(Verdict::Equivalent { vectors: 4, exhaustive: true }));
+    }
+
+    #[test]
+    fn reports_the_first_counterexample() {
+        let Ok(Verdict::Differ(counterexample)) = check(&gate(GateType::Nor, 1), &nand_of_and()) else { panic!() };
+        let column = |name: &str| Column { name: name.to_string(), width: 1 };
+        assert_eq!(counterexample.inputs, [(column("A"), 0), (column("B"), 1)]);
+        assert_eq!(counterexample.outputs, [(column("Y"), Signal::known(0), Signal::known(1))]);
+        assert_eq!(counterexample.to_string(), "A B\n0 1\nY is 0 in the first circuit and 1 in the second");
+    }
+
+    #[test]
+    fn samples_wide_inputs() {
+        let verdict = check(&gate(GateType::Xor, 16), &gate(GateType::Xor, 16));
+        assert_eq!(verdict, Ok(Verdict::Equivalent { vectors: RANDOM_VECTORS, exhaustive: false }));
+
+        let Ok(Verdict::Differ(counterexample)) = check(&gate(GateType::Or, 16), &gate(GateType::Xor, 16)) else {
+            panic!()
+        };
+        let [(_, a), (_, b)] = [&counterexample.inputs[0], &counterexample.inputs[1]];
+        assert_ne!(a & b, 0);
+    }
+
+    #[test]
+    fn oscillations_are_inconclusive() {
+        // Y = !(A & Y) oscillates while A is high
+        let oscillator = || {
+            let mut circuit = Circuit::new();
+            let (a, y) = (circuit.add_pin(), circuit.add_pin());
+            let nand = circuit.add_gate(GateType::Nand);
+            let nand = circuit.gate(nand).unwrap().clone();
+            circuit.connect(a, nand.inputs[0]).unwrap();
+            circuit.connect(nand.outputs[0], nand.inputs[1]).unwrap();
+            circuit.connect(nand.outputs[0], y).unwrap();
+            circuit.set(nand.inputs[1], false);
+            (circuit, Interface::named(&[("A", a)], &[("Y", y)]))
+        };
+        let Ok(Verdict::Inconclusive(unsettled)) = check(&oscillator(), &oscillator()) else { panic!() };
+        assert_eq!(unsettled.settled, [false, false]);
+        assert_eq!(unsettled.to_string(), "A\n1\nNeither circuit settles");
+    }
+
+    #[test]
+    fn interfaces_must_match() {
+        let mut renamed = gate(GateType::Nand, 1);
+        renamed.1.inputs[1].0 = "C".to_string();
+        assert_eq!(
+            check(&gate(GateType::Nand, 1), &renamed),
+            Err(EquivalenceError::Missing { name: "C".to_string(), in_first: false })
+        );
+        assert_eq!(
+            check(&gate(GateType::And, 2), &gate(GateType::And, 1)),
+            Err(EquivalenceError::Width { name: "A".to_string(), first: 2, second: 1 })
+        );
+    }
+}
@@ -0,0 +1,592 @@
+//! Boolean expressions over named variables, written like `(A & B) | !C`.
+//!
+//! From the loosest to the tightest, the operators are `|` (or `+`), `^`, `&` (or `*`) and `!` (or `~`).
+//! Variables are made of letters, digits and underscores, and can't start with a digit. `0` and `1` are constants.
+//!
+//! Expressions can also be read back from the gates driving a pin of a [`Circuit`], see [`Expr::of_pin`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    iter::Peekable,
+    str::CharIndices,
+};
+
+use crate::circuit::{Circuit, GateId, GateType, Interface, PinId};
+
+/// Most operators, variables and constants an expression read back from a circuit can have. Gates whose outputs fan
+/// out and meet again get repeated on every path, which can double the expression at each level.
+pub const MAX_EXTRACTED_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    /// Chains of the same operator are kept together, like `A & B & C`
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { text, chars: text.char_indices().peekable() };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some((position, ')')) => Err(ParseError::Unopened { position }),
+            Some((position, found)) => Err(ParseError::Unexpected { position, found }),
+        }
+    }
+
+    /// Variables in order of first appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = vec![];
+        self.visit_variables(&mut variables);
+        variables
+    }
+
+    fn visit_variables<'a>(&'a self, variables: &mut Vec<&'a str>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Var(name) => {
+                if !variables.contains(&name.as_str()) {
+                    variables.push(name);
+                }
+            }
+            Expr::Not(expr) => expr.visit_variables(variables),
+            Expr::And(exprs) | Expr::Or(exprs) | Expr::Xor(exprs) => {
+                for expr in exprs {
+                    expr.visit_variables(variables);
+                }
+            }
+        }
+    }
+
+    /// Value of the expression, given the value of each variable
+    pub fn evaluate(&self, value: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Const(constant) => *constant,
+            Expr::Var(name) => value(name),
+            Expr::Not(expr) => !expr.evaluate(value),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.evaluate(value)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.evaluate(value)),
+            Expr::Xor(exprs) => exprs.iter().filter(|expr| expr.evaluate(value)).count() % 2 == 1,
+        }
+    }
+
+    /// Expression of the gates driving a one-bit pin, in terms of the inputs of the circuit, gate for gate
+    pub fn of_pin(circuit: &Circuit, interface: &Interface, pin: PinId) -> Result<Self, ExtractError> {
+        Extraction { circuit, interface, visiting: HashSet::new(), extracted: HashMap::new() }.pin(pin)
+    }
+
+    /// Number of operators, variables and constants
+    pub fn size(&self) -> usize {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => 1,
+            Expr::Not(expr) => 1 + expr.size(),
+            Expr::And(exprs) | Expr::Or(exprs) | Expr::Xor(exprs) => 1 + exprs.iter().map(Expr::size).sum::<usize>(),
+        }
+    }
+
+    /// Equivalent expression, with double negations, constants, repeated operands and operands absorbed by others
+    /// removed, and chains of the same operator merged
+    pub fn simplify(&self) -> Self {
+        let mut expr = self.clone();
+        loop {
+            let simplified = expr.clone().simplify_once();
+            if simplified == expr {
+                return expr;
+            }
+            expr = simplified;
+        }
+    }
+
+    fn simplify_once(self) -> Self {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => self,
+            Expr::Not(inner) => match inner.simplify_once() {
+                Expr::Const(constant) => Expr::Const(!constant),
+                Expr::Not(inner) => *inner,
+                inner => Expr::Not(Box::new(inner)),
+            },
+            Expr::And(operands) => Self::simplify_chain(operands, false),
+            Expr::Or(operands) => Self::simplify_chain(operands, true),
+            Expr::Xor(operands) => {
+                let mut parity = false;
+                let mut kept: Vec<Expr> = vec![];
+                for operand in Self::flatten(operands, |expr| matches!(expr, Expr::Xor(_))) {
+                    match operand {
+                        Expr::Const(constant) => parity ^= constant,
+                        // Pairs cancel out
+                        operand => match kept.iter().position(|other| *other == operand) {
+                            Some(idx) => drop(kept.remove(idx)),
+                            None => kept.push(operand),
+                        },
+                    }
+                }
+                let expr = match kept.len() {
+                    0 => return Expr::Const(parity),
+                    1 => kept.pop().unwrap(),
+                    _ => Expr::Xor(kept),
+                };
+                if parity { Expr::Not(Box::new(expr)) } else { expr }
+            }
+        }
+    }
+
+    /// Simplifies the operands of a chain, and moves those of the chains of the same operator up into it
+    fn flatten(operands: Vec<Expr>, same: fn(&Expr) -> bool) -> Vec<Expr> {
+        let mut flat = vec![];
+        for operand in operands.into_iter().map(Expr::simplify_once) {
+            match operand {
+                Expr::And(inner) | Expr::Or(inner) | Expr::Xor(inner) if same(&operand) => flat.extend(inner),
+                operand => flat.push(operand),
+            }
+        }
+        flat
+    }
+
+    /// And chains when `or` is false, Or chains otherwise. Their dominant constant, `or` itself, wins over everything.
+    fn simplify_chain(operands: Vec<Expr>, or: bool) -> Self {
+        let same: fn(&Expr) -> bool = match or {
+            true => |expr| matches!(expr, Expr::Or(_)),
+            false => |expr| matches!(expr, Expr::And(_)),
+        };
+        let group = if or { Expr::Or } else { Expr::And };
+
+        let mut kept: Vec<Expr> = vec![];
+        for operand in Self::flatten(operands, same) {
+            match operand {
+                Expr::Const(constant) if constant == or => return Expr::Const(or),
+                Expr::Const(_) => {}
+                operand if !kept.contains(&operand) => kept.push(operand),
+                _ => {}
+            }
+        }
+
+        // An operand along with its complement decides the chain, like `A & !A`
+        let complemented = kept.iter().any(|operand| kept.contains(&Expr::Not(Box::new(operand.clone()))));
+        if complemented {
+            return Expr::Const(or);
+        }
+
+        // Absorption: `A | (A & B)` is `A`, and `A & (A | B)` is `A`
+        let absorbed = |operand: &Expr| match (operand, or) {
+            (Expr::And(inner), true) | (Expr::Or(inner), false) => {
+                inner.iter().any(|term| kept.iter().any(|other| other == term))
+            }
+            _ => false,
+        };
+        let kept: Vec<_> = kept.iter().filter(|operand| !absorbed(operand)).cloned().collect();
+
+        match kept.len() {
+            0 => Expr::Const(!or),
+            1 => kept.into_iter().next().unwrap(),
+            _ => group(kept),
+        }
+    }
+
+    /// How tightly the expression binds, to know when it needs parentheses
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(_) => 0,
+            Expr::Xor(_) => 1,
+            Expr::And(_) => 2,
+            Expr::Not(_) | Expr::Var(_) | Expr::Const(_) => 3,
+        }
+    }
+}
+
+/// Writes the expression back in the syntax it is parsed from, with only the parentheses it needs
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (operands, operator) = match self {
+            Expr::Const(constant) => return write!(f, "{}", *constant as u8),
+            Expr::Var(name) => return write!(f, "{name}"),
+            Expr::Not(inner) if inner.precedence() < 3 => return write!(f, "!({inner})"),
+            Expr::Not(inner) => return write!(f, "!{inner}"),
+            Expr::And(operands) => (operands, " & "),
+            Expr::Or(operands) => (operands, " | "),
+            Expr::Xor(operands) => (operands, " ^ "),
+        };
+
+        for (idx, operand) in operands.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(operator)?;
+            }
+            // Chains of the same operator inside others keep their parentheses, so they read back the same
+            match operand.precedence() <= self.precedence() {
+                true => write!(f, "({operand})")?,
+                false => write!(f, "{operand}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a pin has no boolean expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// Driven through a flip-flop, latch or clock, whose output depends on more than the current inputs
+    Sequential(GateType),
+    /// Driven through a loop of gates
+    Loop,
+    /// Nothing drives the pin
+    Floating,
+    /// Several wires drive the pin
+    SeveralDrivers,
+    /// The pin, or a gate driving it, carries several bits
+    Bus,
+    /// Driven through a gate with no boolean operator, like a tri-state buffer
+    Unsupported(GateType),
+    /// The expression would be bigger than [`MAX_EXTRACTED_SIZE`]
+    TooLarge,
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Sequential(kind) => write!(f, "it depends on the state of a {}", kind.as_str()),
+            ExtractError::Loop => write!(f, "it is driven through a loop of gates"),
+            ExtractError::Floating => write!(f, "part of it isn't driven by anything"),
+            ExtractError::SeveralDrivers => write!(f, "part of it is driven by several edges"),
+            ExtractError::Bus => write!(f, "it goes through buses"),
+            ExtractError::Unsupported(kind) => write!(f, "{} gates have no boolean operator", kind.as_str()),
+            ExtractError::TooLarge => write!(f, "it has more than {MAX_EXTRACTED_SIZE} terms"),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+/// Walks from a pin back to the inputs of the circuit
+struct Extraction<'a> {
+    circuit: &'a Circuit,
+    interface: &'a Interface,
+    /// Gates between the pin being extracted and the starting one, to find loops
+    visiting: HashSet<GateId>,
+    /// Expressions of the pins already walked, which several gates can be fed by
+    extracted: HashMap<PinId, Expr>,
+}
+
+impl Extraction<'_> {
+    fn pin(&mut self, pin: PinId) -> Result<Expr, ExtractError> {
+        if let Some(expr) = self.extracted.get(&pin) {
+            return Ok(expr.clone());
+        }
+        let expr = self.walk(pin)?;
+        if expr.size() > MAX_EXTRACTED_SIZE {
+            return Err(ExtractError::TooLarge);
+        }
+        self.extracted.insert(pin, expr.clone());
+        Ok(expr)
+    }
+
+    fn walk(&mut self, pin: PinId) -> Result<Expr, ExtractError> {
+        if self.circuit.width(pin) != 1 {
+            return Err(ExtractError::Bus);
+        }
+        if let Some((name, _)) = self.interface.inputs.iter().find(|&&(_, input)| input == pin) {
+            return Ok(Expr::Var(name.clone()));
+        }
+        if let Some((id, gate)) = self.circuit.gates().find(|(_, gate)| gate.outputs.contains(&pin)) {
+            return self.gate(id, gate.kind, &gate.inputs);
+        }
+
+        let mut drivers = self.circuit.wires().filter(|(_, wire)| wire.to == pin);
+        match (drivers.next(), drivers.next()) {
+            (Some((_, wire)), None) => self.pin(wire.from),
+            (Some(_), Some(_)) => Err(ExtractError::SeveralDrivers),
+            (None, _) => Err(ExtractError::Floating),
+        }
+    }
+
+    fn gate(&mut self, id: GateId, kind: GateType, inputs: &[PinId]) -> Result<Expr, ExtractError> {
+        use GateType::*;
+        if kind.is_sequential() {
+            return Err(ExtractError::Sequential(kind));
+        }
+        if !self.visiting.insert(id) {
+            return Err(ExtractError::Loop);
+        }
+
+        let operands = inputs.iter().map(|&input| self.pin(input)).collect::<Result<Vec<_>, _>>()?;
+        self.visiting.remove(&id);
+
+        let not = |expr| Expr::Not(Box::new(expr));
+        Ok(match kind {
+            And => Expr::And(operands),
+            Or => Expr::Or(operands),
+            Xor => Expr::Xor(operands),
+            Nand => not(Expr::And(operands)),
+            Nor => not(Expr::Or(operands)),
+            Xnor => not(Expr::Xor(operands)),
+            Not => not(operands.into_iter().next().unwrap()),
+            Buffer => operands.into_iter().next().unwrap(),
+            TriState | Splitter | Merger | Custom(_) | SrLatch | DFlipFlop | JkFlipFlop | TFlipFlop | Clock(_) => {
+                return Err(ExtractError::Unsupported(kind))
+            }
+        })
+    }
+}
+
+/// Where and why an expression couldn't be parsed. Positions count characters from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A character that can't go there
+    Unexpected { position: usize, found: char },
+    /// The expression stops where an operand was expected
+    MissingOperand { position: usize },
+    /// A `(` that is never closed
+    Unclosed { position: usize },
+    /// A `)` that doesn't close anything
+    Unopened { position: usize },
+}
+
+impl ParseError {
+    pub fn position(&self) -> usize {
+        match *self {
+            ParseError::Unexpected { position, .. }
+            | ParseError::MissingOperand { position }
+            | ParseError::Unclosed { position }
+            | ParseError::Unopened { position } => position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.position() + 1;
+        match self {
+            ParseError::Unexpected { found, .. } => write!(f, "unexpected '{found}' at column {column}"),
+            ParseError::MissingOperand { .. } => write!(f, "missing operand at column {column}"),
+            ParseError::Unclosed { .. } => write!(f, "the '(' at column {column} is never closed"),
+            ParseError::Unopened { .. } => write!(f, "the ')' at column {column} doesn't close anything"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Recursive descent, one function per level of precedence
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    /// Next character that isn't whitespace, with its position in characters
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let &(byte, c) = self.chars.peek()?;
+        Some((self.text[..byte].chars().count(), c))
+    }
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        let next = self.peek()?;
+        self.chars.next();
+        Some(next)
+    }
+
+    /// Position right after the last character, where an operand is missing
+    fn end(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Operands separated by any of the given operators, grouped together if there are several
+    fn chain(
+        &mut self,
+        operators: &[char],
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+        group: fn(Vec<Expr>) -> Expr,
+    ) -> Result<Expr, ParseError> {
+        let mut operands = vec![operand(self)?];
+        while self.peek().is_some_and(|(_, c)| operators.contains(&c)) {
+            self.next();
+            operands.push(operand(self)?);
+        }
+        Ok(if operands.len() == 1 { operands.pop().unwrap() } else { group(operands) })
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        self.chain(&['|', '+'], Self::xor, Expr::Or)
+    }
+
+    fn xor(&mut self) -> Result<Expr, ParseError> {
+        self.chain(&['^'], Self::and, Expr::Xor)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        self.chain(&['&', '*'], Self::not, Expr::And)
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some((_, '!' | '~')) => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.not()?)))
+            }
+            _ => self.operand(),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Expr, ParseError> {
+        let Some((position, c)) = self.next() else {
+            return Err(ParseError::MissingOperand { position: self.end() });
+        };
+        match c {
+            '(' => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some((_, ')')) => Ok(expr),
+                    Some((position, found)) => Err(ParseError::Unexpected { position, found }),
+                    None => Err(ParseError::Unclosed { position }),
+                }
+            }
+            '0' => Ok(Expr::Const(false)),
+            '1' => Ok(Expr::Const(true)),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some((_, c)) = self.chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_') {
+                    name.push(c);
+                }
+                Ok(Expr::Var(name))
+            }
+            found => Err(ParseError::Unexpected { position, found }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Expr {
+        Expr::Var(name.to_string())
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        let expr = Expr::parse("(A & B) | !C").unwrap();
+        assert_eq!(expr, Expr::Or(vec![Expr::And(vec![var("A"), var("B")]), Expr::Not(Box::new(var("C")))]));
+
+        let expr = Expr::parse("a | b ^ c & ~d & 1").unwrap();
+        let and = Expr::And(vec![var("c"), Expr::Not(Box::new(var("d"))), Expr::Const(true)]);
+        assert_eq!(expr, Expr::Or(vec![var("a"), Expr::Xor(vec![var("b"), and])]));
+
+        assert_eq!(Expr::parse("!!(x_1)").unwrap(), Expr::Not(Box::new(Expr::Not(Box::new(var("x_1"))))));
+        assert_eq!(expr.variables(), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn evaluates_every_operator() {
+        let expr = Expr::parse("A ^ B ^ C | !(A + B) * C").unwrap();
+        let table: Vec<_> = (0..8)
+            .map(|row: u32| expr.evaluate(&|name| row >> (2 - (name.as_bytes()[0] - b'A')) & 1 == 1))
+            .collect();
+        assert_eq!(table, [false, true, true, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_character() {
+        assert_eq!(Expr::parse("A & ? B"), Err(ParseError::Unexpected { position: 4, found: '?' }));
+        assert_eq!(Expr::parse("A |"), Err(ParseError::MissingOperand { position: 3 }));
+        assert_eq!(Expr::parse("(A & (B)"), Err(ParseError::Unclosed { position: 0 }));
+        assert_eq!(Expr::parse("A)"), Err(ParseError::Unopened { position: 1 }));
+        assert_eq!(Expr::parse("A B"), Err(ParseError::Unexpected { position: 2, found: 'B' }));
+        assert_eq!(Expr::parse("é & ?").unwrap_err().to_string(), "unexpected '?' at column 5");
+    }
+
+    fn simplified(text: &str) -> String {
+        Expr::parse(text).unwrap().simplify().to_string()
+    }
+
+    #[test]
+    fn prints_back_what_it_parses() {
+        for text in ["(A | B) & !C", "A ^ B & C | D", "!(A & B) ^ 1", "(A | B) | C", "!!A"] {
+            assert_eq!(Expr::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn simplifies_redundant_terms() {
+        assert_eq!(simplified("!!A & 1"), "A");
+        assert_eq!(simplified("(A | B) | C & C"), "A | B | C");
+        assert_eq!(simplified("A & !A | B"), "B");
+        assert_eq!(simplified("A | !A & B"), "A | !A & B");
+        assert_eq!(simplified("A | A & B"), "A");
+        assert_eq!(simplified("A & (B | A)"), "A");
+        assert_eq!(simplified("A ^ B ^ A ^ 1"), "!B");
+        assert_eq!(simplified("!(A | 1)"), "0");
+        assert_eq!(simplified("(A & 0) ^ (B | 0)"), "B");
+    }
+
+    #[test]
+    fn extracts_combinational_outputs() {
+        let mut circuit = Circuit::new();
+        let (a, b, sum, carry) = (circuit.add_pin(), circuit.add_pin(), circuit.add_pin(), circuit.add_pin());
+        let (xor, nand, not) =
+            (circuit.add_gate(GateType::Xor), circuit.add_gate(GateType::Nand), circuit.add_gate(GateType::Not));
+        let [xor, nand, not] = circuit.gates_of([xor, nand, not]);
+        for gate in [&xor, &nand] {
+            circuit.connect(a, gate.inputs[0]).unwrap();
+            circuit.connect(b, gate.inputs[1]).unwrap();
+        }
+        circuit.connect(xor.outputs[0], sum).unwrap();
+        circuit.connect(nand.outputs[0], not.inputs[0]).unwrap();
+        circuit.connect(not.outputs[0], carry).unwrap();
+
+        let interface = Interface::named(&[("A", a), ("B", b)], &[("S", sum), ("C", carry)]);
+        assert_eq!(Expr::of_pin(&circuit, &interface, sum).unwrap().to_string(), "A ^ B");
+        let carry = Expr::of_pin(&circuit, &interface, carry).unwrap();
+        assert_eq!(carry.to_string(), "!!(A & B)");
+        assert_eq!(carry.simplify().to_string(), "A & B");
+    }
+
+    #[test]
+    fn sequential_and_looping_outputs_have_no_expression() {
+        let mut circuit = Circuit::new();
+        let (input, latched, looped, floating) =
+            (circuit.add_pin(), circuit.add_pin(), circuit.add_pin(), circuit.add_pin());
+        let latch = circuit.add_gate(GateType::SrLatch);
+        let (or, and) = (circuit.add_gate(GateType::Or), circuit.add_gate(GateType::And));
+        let [latch, or, and] = circuit.gates_of([latch, or, and]);
+        circuit.connect(input, latch.inputs[0]).unwrap();
+        circuit.connect(latch.outputs[0], latched).unwrap();
+        // Or gate holding itself high
+        circuit.connect(input, or.inputs[0]).unwrap();
+        circuit.connect(or.outputs[0], or.inputs[1]).unwrap();
+        circuit.connect(or.outputs[0], looped).unwrap();
+        circuit.connect(input, and.inputs[0]).unwrap();
+        circuit.connect(and.outputs[0], floating).unwrap();
+
+        let interface = Interface::named(&[("A", input)], &[]);
+        let extract = |pin| Expr::of_pin(&circuit, &interface, pin);
+        assert_eq!(extract(latched), Err(ExtractError::Sequential(GateType::SrLatch)));
+        assert_eq!(extract(looped), Err(ExtractError::Loop));
+        assert_eq!(extract(floating), Err(ExtractError::Floating));
+    }
+
+    #[test]
+    fn reconverging_fanout_is_walked_once() {
+        // Each And gate is fed twice by the previous one, doubling the expression at each level
+        let mut circuit = Circuit::new();
+        let input = circuit.add_pin();
+        let mut levels = vec![input];
+        for _ in 0..64 {
+            let and = circuit.add_gate(GateType::And);
+            let and = circuit.gate(and).unwrap().clone();
+            circuit.connect(*levels.last().unwrap(), and.inputs[0]).unwrap();
+            circuit.connect(*levels.last().unwrap(), and.inputs[1]).unwrap();
+            levels.push(and.outputs[0]);
+        }
+
+        let interface = Interface::named(&[("A", input)], &[]);
+        let expr = Expr::of_pin(&circuit, &interface, levels[3]).unwrap();
+        assert_eq!(expr.to_string(), "((A & A) & (A & A)) & ((A & A) & (A & A))");
+        assert_eq!(expr.simplify().to_string(), "A");
+        assert_eq!(Expr::of_pin(&circuit, &interface, levels[64]), Err(ExtractError::TooLarge));
+    }
+}
@@ -0,0 +1,554 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use bevy::{
+    ecs::{schedule::ShouldRun, system::SystemParam},
+    prelude::*,
+};
+use logic_sim::save::{CircuitFile, ComponentSave, EdgeSave, GateSave, InputSave, OutputSave, PinRef};
+
+use crate::{
+    circuit::Signal,
+    compare::CheckEquivalence,
+    component::{ExportLibrary, ImportLibrary, Library, MakeComponent},
+    constants::Colors,
+    edge::{Edge, EdgeBundle, EdgeSignal},
+    gate::{Gate, GateBundle},
+    history::History,
+    node::Node,
+    simulation::{DelaySettings, DriverResolution},
+    synthesis::BuildExpression,
+    table::ExportTruthTable,
+    ui::{
+        next_label, spawn_panel_node, text_builder, InputNodeMarker, NodeLabel, OutputNodeMarker, Panel,
+        PanelRootMarker, RemoveNodeMarker,
+    },
+};
+
+pub struct FilePlugin;
+
+impl Plugin for FilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CurrentFile(None))
+            .insert_resource(PathPrompt(None))
+            .insert_resource(FileStatus(String::new()))
+            .add_event::<FileCommand>()
+            .add_event::<SaveCircuit>()
+            .add_event::<LoadCircuit>()
+            .add_startup_system(create_file_ui)
+            .add_system(interact_file_ui)
+            .add_system(file_shortcuts.with_run_criteria(prompt_closed))
+            .add_system(handle_file_commands)
+            .add_system(edit_path_prompt)
+            .add_system(show_file_status)
+            .add_system(save_circuit)
+            .add_system(load_circuit);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCommand {
+    Open,
+    Save,
+    SaveAs,
+    /// Asks for the name of a new component made from the selection
+    MakeComponent,
+    ImportLibrary,
+    /// Writes the components made in this circuit to a library file
+    ExportLibrary,
+    /// Writes the last truth table to a CSV file, from the truth table panel
+    ExportTruthTable,
+    /// Asks for a boolean expression to build out of gates
+    BuildExpression,
+    /// Asks for two circuits to compare
+    CheckEquivalence,
+}
+
+impl FileCommand {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FileCommand::Open => "Open",
+            FileCommand::Save => "Save",
+            FileCommand::SaveAs => "Save As",
+            FileCommand::MakeComponent => "Component",
+            FileCommand::ImportLibrary => "Import",
+            FileCommand::ExportLibrary => "Export",
+            FileCommand::ExportTruthTable => "Export CSV",
+            FileCommand::BuildExpression => "Expression",
+            FileCommand::CheckEquivalence => "Compare",
+        }
+    }
+}
+
+pub struct SaveCircuit(pub PathBuf);
+
+pub struct LoadCircuit(pub PathBuf);
+
+/// Path of the file the circuit was last saved to or loaded from
+#[derive(Resource)]
+pub struct CurrentFile(pub Option<PathBuf>);
+
+/// Path (or component name, expression, or pair of circuits) being typed by the user, and what to do with it once
+/// they press enter
+#[derive(Resource)]
+pub struct PathPrompt(pub Option<(FileCommand, String)>);
+
+/// Runs keyboard shortcuts only while the prompt is closed, since their keys are being typed into it otherwise
+pub fn prompt_closed(prompt: Res<PathPrompt>) -> ShouldRun {
+    match prompt.0 {
+        Some(_) => ShouldRun::No,
+        None => ShouldRun::Yes,
+    }
+}
+
+/// Message shown next to the file buttons
+#[derive(Resource)]
+pub struct FileStatus(pub String);
+
+#[derive(Component)]
+struct FileButton(FileCommand);
+
+#[derive(Component)]
+struct FileStatusMarker;
+
+fn create_file_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Px(40.0)),
+        margin: UiRect::all(Val::Px(10.0)),
+        padding: UiRect::horizontal(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(75.0),
+                    top: Val::Px(0.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                justify_content: JustifyContent::FlexStart,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Colors::UI_BG.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            use FileCommand::*;
+            let commands =
+                [Open, Save, SaveAs, MakeComponent, ImportLibrary, ExportLibrary, BuildExpression, CheckEquivalence];
+            for command in commands {
+                c.spawn((
+                    ButtonBundle {
+                        style: button_style.clone(),
+                        background_color: Colors::OFF.into(),
+                        ..default()
+                    },
+                    FileButton(command),
+                ))
+                .with_children(|c| {
+                    c.spawn(text_builder(command.as_str(), &asset_server));
+                });
+            }
+
+            c.spawn((text_builder("", &asset_server), FileStatusMarker));
+        });
+}
+
+fn interact_file_ui(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &FileButton), Changed<Interaction>>,
+    mut file_commands: EventWriter<FileCommand>,
+) {
+    for (interaction, mut color, &FileButton(command)) in &mut query {
+        match *interaction {
+            Interaction::None => *color = Colors::OFF.into(),
+            Interaction::Hovered => *color = Colors::highlighted(false).into(),
+            Interaction::Clicked => {
+                *color = Colors::ON.into();
+                file_commands.send(command);
+            }
+        }
+    }
+}
+
+fn file_shortcuts(keys: Res<Input<KeyCode>>, mut file_commands: EventWriter<FileCommand>) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if !ctrl {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::O) {
+        file_commands.send(FileCommand::Open);
+    } else if keys.just_pressed(KeyCode::S) {
+        file_commands.send(if shift { FileCommand::SaveAs } else { FileCommand::Save });
+    }
+}
+
+fn handle_file_commands(
+    mut file_commands: EventReader<FileCommand>,
+    mut prompt: ResMut<PathPrompt>,
+    mut save: EventWriter<SaveCircuit>,
+    current: Res<CurrentFile>,
+    library: Res<Library>,
+) {
+    for &command in file_commands.iter() {
+        match (command, &current.0) {
+            (FileCommand::Save, Some(path)) => save.send(SaveCircuit(path.clone())),
+            (FileCommand::MakeComponent, _) => {
+                let name = format!("Component{}", library.0.len() + 1);
+                prompt.0 = Some((command, name));
+            }
+            (FileCommand::ImportLibrary | FileCommand::ExportLibrary, _) => {
+                prompt.0 = Some((command, "library.ron".into()));
+            }
+            (FileCommand::ExportTruthTable, _) => prompt.0 = Some((command, "truth_table.csv".into())),
+            (FileCommand::BuildExpression, _) => prompt.0 = Some((command, String::new())),
+            (FileCommand::CheckEquivalence, path) => {
+                let path = path.as_ref().map_or("circuit.ron".into(), |path| path.display().to_string());
+                prompt.0 = Some((command, format!("{path} == ")));
+            }
+            // Saving a circuit that was never saved asks for a path first
+            (command, path) => {
+                let command = if command == FileCommand::Save { FileCommand::SaveAs } else { command };
+                let path = path.as_ref().map_or("circuit.ron".into(), |path| path.display().to_string());
+                prompt.0 = Some((command, path));
+            }
+        }
+    }
+}
+
+/// Where the text of the prompt goes once it is entered, depending on the command
+#[derive(SystemParam)]
+struct PromptTargets<'w, 's> {
+    save: EventWriter<'w, 's, SaveCircuit>,
+    load: EventWriter<'w, 's, LoadCircuit>,
+    make_component: EventWriter<'w, 's, MakeComponent>,
+    import_library: EventWriter<'w, 's, ImportLibrary>,
+    export_library: EventWriter<'w, 's, ExportLibrary>,
+    export_table: EventWriter<'w, 's, ExportTruthTable>,
+    build_expression: EventWriter<'w, 's, BuildExpression>,
+    check_equivalence: EventWriter<'w, 's, CheckEquivalence>,
+}
+
+impl<'w, 's> PromptTargets<'w, 's> {
+    fn send(&mut self, command: FileCommand, text: &str) {
+        let path = PathBuf::from(text);
+        match command {
+            FileCommand::Open => self.load.send(LoadCircuit(path)),
+            FileCommand::Save | FileCommand::SaveAs => self.save.send(SaveCircuit(path)),
+            FileCommand::MakeComponent => self.make_component.send(MakeComponent(text.to_string())),
+            FileCommand::ImportLibrary => self.import_library.send(ImportLibrary(path)),
+            FileCommand::ExportLibrary => self.export_library.send(ExportLibrary(path)),
+            FileCommand::ExportTruthTable => self.export_table.send(ExportTruthTable(path)),
+            FileCommand::BuildExpression => self.build_expression.send(BuildExpression(text.to_string())),
+            FileCommand::CheckEquivalence => self.check_equivalence.send(CheckEquivalence(text.to_string())),
+        }
+    }
+}
+
+/// Lets the user type a path while the prompt is open
+fn edit_path_prompt(
+    mut prompt: ResMut<PathPrompt>,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut targets: PromptTargets,
+) {
+    let Some((command, ref mut text)) = prompt.0 else {
+        characters.clear();
+        return;
+    };
+
+    for ev in characters.iter() {
+        if !ev.char.is_control() {
+            text.push(ev.char);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        text.pop();
+    } else if keys.just_pressed(KeyCode::Escape) {
+        prompt.0 = None;
+    } else if keys.just_pressed(KeyCode::Return) {
+        targets.send(command, text);
+        prompt.0 = None;
+    }
+}
+
+fn show_file_status(
+    prompt: Res<PathPrompt>,
+    status: Res<FileStatus>,
+    mut text: Query<&mut Text, With<FileStatusMarker>>,
+) {
+    if !prompt.is_changed() && !status.is_changed() {
+        return;
+    }
+
+    let mut text = text.single_mut();
+    text.sections[0].value = match &prompt.0 {
+        Some((command, path)) => format!("{}: {path}_", command.as_str()),
+        None => status.0.clone(),
+    };
+}
+
+/// Nodes around selected gates turned into a circuit of their own, in the order of its side panels
+pub struct Boundary {
+    /// Nodes outside of the selection feeding each input
+    pub inputs: Vec<Entity>,
+    /// Nodes of the selected gates feeding each output
+    pub outputs: Vec<Entity>,
+}
+
+/// Read access to everything that makes up the circuit, to turn it into a [`CircuitFile`]
+#[derive(SystemParam)]
+pub struct CircuitSnapshot<'w, 's> {
+    roots: Query<'w, 's, (&'static PanelRootMarker, &'static Children)>,
+    buttons: Query<'w, 's, &'static RemoveNodeMarker>,
+    panel_nodes: Query<'w, 's, (&'static Node, &'static NodeLabel)>,
+    nodes: Query<'w, 's, &'static Node>,
+    gates: Query<'w, 's, (&'static Gate, &'static Transform)>,
+    edges: Query<'w, 's, (&'static Edge, &'static EdgeSignal)>,
+    library: Res<'w, Library>,
+    delays: Res<'w, DelaySettings>,
+    resolution: Res<'w, DriverResolution>,
+}
+
+impl<'w, 's> CircuitSnapshot<'w, 's> {
+    pub fn to_file(&self) -> CircuitFile {
+        let mut file = CircuitFile::default();
+        let mut pins = HashMap::new();
+
+        // Follows the order of the panels, which isn't the same as the order of the query
+        for (&PanelRootMarker(panel), children) in self.roots.iter() {
+            for &RemoveNodeMarker(node) in self.buttons.iter_many(children) {
+                let Ok((&Node { value, width }, label)) = self.panel_nodes.get(node) else { continue };
+                let label = label.0.clone();
+
+                match panel {
+                    Panel::Input => {
+                        pins.insert(node, PinRef::Input(file.inputs.len()));
+                        file.inputs.push(InputSave { label, value: value.bits, width });
+                    }
+                    Panel::Output => {
+                        pins.insert(node, PinRef::Output(file.outputs.len()));
+                        file.outputs.push(OutputSave { label, width });
+                    }
+                }
+            }
+        }
+
+        self.add_gates(&mut file, &mut pins, self.gates.iter());
+
+        for (&Edge { from, to }, signal) in self.edges.iter() {
+            let (Some(&from), Some(&to)) = (pins.get(&from), pins.get(&to)) else { continue };
+            file.edges.push(EdgeSave { from, to, delay: signal.delay });
+        }
+
+        file.components = self.library.0.iter().map(ComponentSave::stub).collect();
+        file.delays = self.delays.0.clone();
+        file.resolution = self.resolution.0;
+        file
+    }
+
+    /// Turns the given gates into a circuit of their own. Nodes outside of it that feed its gates become inputs,
+    /// and pins of its gates that feed the outside become outputs.
+    pub fn selection_to_file(&self, selection: &[Entity]) -> (CircuitFile, Boundary) {
+        let mut file = CircuitFile { resolution: self.resolution.0, ..Default::default() };
+        let mut boundary = Boundary { inputs: vec![], outputs: vec![] };
+        let mut pins = HashMap::new();
+
+        self.add_gates(&mut file, &mut pins, self.gates.iter_many(selection));
+
+        for (&Edge { from, to }, signal) in self.edges.iter() {
+            let (from, to) = match (pins.get(&from), pins.get(&to)) {
+                (Some(&from), Some(&to)) => (from, to),
+                (None, Some(&to @ PinRef::GateInput { .. })) => {
+                    let from = *pins.entry(from).or_insert_with(|| {
+                        let used: Vec<_> = file.inputs.iter().map(|input| input.label.as_str()).collect();
+                        let label = next_label(Panel::Input, &used);
+                        let &Node { value, width } = self.nodes.get(from).unwrap();
+                        file.inputs.push(InputSave { label, value: value.bits, width });
+                        boundary.inputs.push(from);
+                        PinRef::Input(file.inputs.len() - 1)
+                    });
+                    (from, to)
+                }
+                (Some(&driver @ PinRef::GateOutput { .. }), None) => {
+                    // The pin may already feed an output
+                    let output =
+                        file.edges.iter().find(|edge| edge.from == driver && matches!(edge.to, PinRef::Output(_)));
+                    if output.is_some() {
+                        continue;
+                    }
+
+                    let used: Vec<_> = file.outputs.iter().map(|output| output.label.as_str()).collect();
+                    let label = next_label(Panel::Output, &used);
+                    let width = self.nodes.get(to).unwrap().width;
+                    file.outputs.push(OutputSave { label, width });
+                    boundary.outputs.push(from);
+                    (driver, PinRef::Output(file.outputs.len() - 1))
+                }
+                _ => continue,
+            };
+            file.edges.push(EdgeSave { from, to, delay: signal.delay });
+        }
+
+        (file, boundary)
+    }
+
+    /// Adds gates to the file, remembering which pin each of their nodes is
+    fn add_gates<'a>(
+        &self,
+        file: &mut CircuitFile,
+        pins: &mut HashMap<Entity, PinRef>,
+        gates: impl Iterator<Item = (&'a Gate, &'a Transform)>,
+    ) {
+        for (gate, transform) in gates {
+            let idx = file.gates.len();
+            for (pin, &input) in gate.inputs.iter().enumerate() {
+                pins.insert(input, PinRef::GateInput { gate: idx, pin });
+            }
+            for (pin, &output) in gate.outputs.iter().enumerate() {
+                pins.insert(output, PinRef::GateOutput { gate: idx, pin });
+            }
+
+            file.gates.push(GateSave {
+                kind: gate.kind,
+                width: gate.width,
+                inputs: gate.inputs.len(),
+                state: gate.state,
+                pos: transform.translation.truncate().into(),
+                size: gate.size.into(),
+                delay: gate.delay,
+            });
+        }
+    }
+}
+
+fn save_circuit(
+    mut events: EventReader<SaveCircuit>,
+    mut current: ResMut<CurrentFile>,
+    mut status: ResMut<FileStatus>,
+    snapshot: CircuitSnapshot,
+) {
+    for SaveCircuit(path) in events.iter() {
+        match snapshot.to_file().save(path) {
+            Ok(()) => {
+                status.0 = format!("Saved to {}", path.display());
+                current.0 = Some(path.clone());
+            }
+            Err(e) => status.0 = format!("Could not save to {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Entities making up the circuit, along with the buttons removing its panel nodes
+type CircuitEntities =
+    Or<(With<Gate>, With<Edge>, With<InputNodeMarker>, With<OutputNodeMarker>, With<RemoveNodeMarker>)>;
+
+/// Everything a loaded circuit replaces: the entities making up the current one, and what is saved along with them
+#[derive(SystemParam)]
+struct Replaced<'w, 's> {
+    old: Query<'w, 's, Entity, CircuitEntities>,
+    history: ResMut<'w, History>,
+    library: ResMut<'w, Library>,
+    delays: ResMut<'w, DelaySettings>,
+    resolution: ResMut<'w, DriverResolution>,
+}
+
+fn load_circuit(
+    mut events: EventReader<LoadCircuit>,
+    mut commands: Commands,
+    mut current: ResMut<CurrentFile>,
+    mut status: ResMut<FileStatus>,
+    replaced: Replaced,
+    roots: Query<(Entity, &PanelRootMarker)>,
+    asset_server: Res<AssetServer>,
+) {
+    let Replaced { old, mut history, mut library, mut delays, mut resolution } = replaced;
+    for LoadCircuit(path) in events.iter() {
+        let loaded = CircuitFile::load(path).and_then(|mut file| {
+            let changed = file.link_libraries()?;
+            Ok((file, changed))
+        });
+        let (file, changed) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                status.0 = format!("Could not open {}: {e}", path.display());
+                continue;
+            }
+        };
+
+        for entity in old.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        // The edits refer to entities that don't exist anymore
+        history.clear();
+        library.0 = file.components;
+        delays.0 = file.delays;
+        resolution.0 = file.resolution;
+
+        let root = |panel| roots.iter().find(|(_, root)| root.0 == panel).unwrap().0;
+        let inputs: Vec<_> = file
+            .inputs
+            .into_iter()
+            .map(|input| {
+                let root = root(Panel::Input);
+                let node = Node { value: Signal::known(input.value), width: input.width };
+                spawn_panel_node(&mut commands, Panel::Input, root, &asset_server, None, input.label, node)
+            })
+            .collect();
+        let outputs: Vec<_> = file
+            .outputs
+            .into_iter()
+            .map(|output| {
+                let root = root(Panel::Output);
+                let node = Node::new(output.width);
+                spawn_panel_node(&mut commands, Panel::Output, root, &asset_server, None, output.label, node)
+            })
+            .collect();
+
+        let gates: Vec<_> = file
+            .gates
+            .into_iter()
+            .map(|gate| {
+                let (_, inputs, outputs) = GateBundle::new(&asset_server, &library, gate.kind, gate.size.into())
+                    .width(gate.width)
+                    .inputs(gate.inputs)
+                    .state(gate.state)
+                    .delay(gate.delay)
+                    .pos(gate.pos.into())
+                    .spawn(&mut commands);
+                (inputs, outputs)
+            })
+            .collect();
+
+        // The file was validated when loading, so every pin exists
+        let entity = |pin: PinRef| match pin {
+            PinRef::Input(idx) => inputs[idx],
+            PinRef::Output(idx) => outputs[idx],
+            PinRef::GateInput { gate, pin } => gates[gate].0[pin],
+            PinRef::GateOutput { gate, pin } => gates[gate].1[pin],
+        };
+
+        for EdgeSave { from, to, delay } in file.edges {
+            commands.spawn(EdgeBundle::new(entity(from), entity(to)).delay(delay));
+        }
+
+        status.0 = match changed.is_empty() {
+            true => format!("Opened {}", path.display()),
+            false => format!(
+                "Opened {}, but the pins of {} changed in their library",
+                path.display(),
+                changed.join(", ")
+            ),
+        };
+        current.0 = Some(path.clone());
+    }
+}
@@ -1,44 +1,100 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_prototype_lyon::entity::ShapeBundle;
 use bevy_prototype_lyon::prelude::*;
 use bevy_prototype_lyon::shapes::Rectangle;
+use logic_sim::truth_table::SETTLE_STEPS;
 
-use crate::constants::Depth;
+use crate::circuit::{ClockConfig, GateState, GateType, Signal};
+use crate::component::{Library, Subcircuit};
+use crate::constants::{Colors, Depth, RADIUS};
 use crate::cursor::Cursor;
-use crate::node::{Node, NodeSpawner};
+use crate::diagnostics::Diagnostics;
+use crate::edge::{Edge, EdgeBundle, EdgeSignal};
+use crate::file::prompt_closed;
+use crate::history::{Edit, EdgeRecord, GateRecord, History};
+use crate::node::{Node, NodeSpawner, PinDirection};
 
 pub struct GatePlugin;
 
 impl Plugin for GatePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MovingGate(None))
+            .insert_resource(HoveredGate(None))
+            .insert_resource(Selection(vec![]))
+            .add_system(hover_gate)
+            .add_system(select_gate.before(move_gate))
+            .add_system(show_selection)
             .add_system(move_gate)
+            .add_system(delete_gate.with_run_criteria(prompt_closed))
+            .add_system(edit_gate.with_run_criteria(prompt_closed))
+            .add_system(configure_clock.with_run_criteria(prompt_closed))
             // .add_system(move_gate_nodes)
-            .add_system(process_gates);
+            .add_system(show_clock_phase);
     }
 }
 
+/// Size of a newly placed gate, before it grows to fit its inputs
+pub const GATE_SIZE: Vec2 = Vec2::splat(120.0);
+
+/// Vertical distance between two input nodes
+const PIN_SPACING: f32 = RADIUS * 2.5;
+
+/// Color of gates, lighter when they are selected
+const GATE_COLOR: Color = Color::PURPLE;
+
+/// Width of gates whose pins are labeled, so the labels don't overlap the name
+const LABELED_GATE_WIDTH: f32 = 200.0;
+
+/// Vertical offset of the `idx`-th of `count` pins, from top to bottom, on a side of a gate of the given height
+fn pin_offset(idx: usize, count: usize, height: f32) -> f32 {
+    (count - idx) as f32 / (count as f32 + 1.0) * height - height / 2.0
+}
+
 // #[derive(Bundle)]
 pub struct GateBundle {
     pub size: Vec2,
     pub kind: GateType,
+    pub width: u8,
+    pub num_inputs: usize,
+    pub state: GateState,
+    /// Delay in ticks, if it isn't the one of its type
+    pub delay: Option<u32>,
+    /// Definition of custom components
+    subcircuit: Option<Subcircuit>,
     shape: ShapeBundle,
     text: Text2dBundle,
 }
 
 impl GateBundle {
-    pub fn new(asset_server: &Res<AssetServer>, kind: GateType, size: Vec2) -> Self {
-        let kind_name = kind.as_str();
+    pub fn new(asset_server: &Res<AssetServer>, library: &Library, kind: GateType, mut size: Vec2) -> Self {
+        let (kind_name, subcircuit) = match kind {
+            GateType::Custom(idx) => (library.0[idx].name.as_str(), Some(Subcircuit::new(&library.0, idx))),
+            _ => (kind.as_str(), None),
+        };
+
+        if !kind.input_names().is_empty() || subcircuit.is_some() {
+            size.x = size.x.max(LABELED_GATE_WIDTH);
+        }
 
+        let width = kind.default_width();
+        let num_inputs = match &subcircuit {
+            Some(subcircuit) => subcircuit.interface.inputs.len(),
+            None => kind.default_inputs(width),
+        };
         Self {
             size,
             kind,
+            width,
+            num_inputs,
+            state: GateState::default(),
+            delay: None,
+            subcircuit,
             shape: GeometryBuilder::build_as(
                 &Rectangle {
                     origin: RectangleOrigin::Center,
                     extents: size,
                 },
-                DrawMode::Fill(FillMode::color(Color::PURPLE)),
+                DrawMode::Fill(FillMode::color(GATE_COLOR)),
                 Transform::from_xyz(0.0, 0.0, Depth::GATE),
             ),
             text: Text2dBundle {
@@ -58,6 +114,77 @@ impl GateBundle {
                 ..Default::default()
             },
         }
+        .fit()
+    }
+
+    /// Sets the number of inputs, and makes the gate taller if they don't fit
+    pub fn inputs(mut self, num_inputs: usize) -> Self {
+        self.num_inputs = num_inputs;
+        self.fit()
+    }
+
+    /// Sets the number of bits, which may change the number of inputs of mergers and outputs of splitters.
+    /// Custom components keep the widths of their definition.
+    pub fn width(mut self, width: u8) -> Self {
+        if self.subcircuit.is_some() {
+            return self;
+        }
+
+        self.width = width;
+        if !self.kind.input_range(width).contains(&self.num_inputs) {
+            self.num_inputs = self.kind.default_inputs(width);
+        }
+
+        let name = self.kind.as_str();
+        self.text.text.sections[0].value = match width {
+            1 => name.to_string(),
+            _ => format!("{name}\n{width} bits"),
+        };
+        self.fit()
+    }
+
+    /// Widths of the inputs and outputs
+    fn pin_widths(&self) -> (Vec<u8>, Vec<u8>) {
+        match &self.subcircuit {
+            Some(subcircuit) => subcircuit.pin_widths(),
+            None => (self.kind.input_widths(self.num_inputs, self.width), self.kind.output_widths(self.width)),
+        }
+    }
+
+    /// Labels shown next to the pins, if any
+    fn pin_names(&self) -> (Vec<String>, Vec<String>) {
+        match &self.subcircuit {
+            Some(subcircuit) => subcircuit.pin_names(),
+            None => {
+                let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+                let inputs = &self.kind.input_names()[..self.kind.input_names().len().min(self.num_inputs)];
+                (names(inputs), names(self.kind.output_names()))
+            }
+        }
+    }
+
+    /// Makes the gate taller if its pins don't fit
+    fn fit(mut self) -> Self {
+        let pins = self.num_inputs.max(self.pin_widths().1.len());
+        self.size.y = self.size.y.max((pins + 1) as f32 * PIN_SPACING);
+        self.shape.path = ShapePath::build_as(&Rectangle {
+            origin: RectangleOrigin::Center,
+            extents: self.size,
+        });
+
+        self
+    }
+
+    pub fn state(mut self, state: GateState) -> Self {
+        self.state = state;
+
+        self
+    }
+
+    pub fn delay(mut self, delay: Option<u32>) -> Self {
+        self.delay = delay;
+
+        self
     }
 
     pub fn pos(mut self, pos: Vec2) -> Self {
@@ -66,132 +193,470 @@ impl GateBundle {
         self
     }
 
-    pub fn spawn<'w, 's, 'a>(
-        self,
-        commands: &'a mut Commands<'w, 's>,
-    ) -> bevy::ecs::system::EntityCommands<'w, 's, 'a> {
-        let num_inputs = self.kind.num_inputs();
-        let inputs = (0..self.kind.num_inputs())
-            .rev()
-            .map(|idx| {
-                let node = NodeSpawner::from_pos(Vec2::new(
-                    -self.size.x / 2.0,
-                    (idx as f32 + 1.0) / (num_inputs as f32 + 1.0) * self.size.y
-                        - self.size.y / 2.0,
-                ));
-                commands.spawn(node).id()
-            })
-            .collect::<Vec<_>>();
-
-        let output = commands
-            .spawn(NodeSpawner::from_pos(Vec2::new(self.size.x / 2.0, 0.0)))
-            .id();
+    /// Spawns the gate and its nodes, and returns the gate, its input nodes and its output nodes
+    pub fn spawn(self, commands: &mut Commands) -> (Entity, Vec<Entity>, Vec<Entity>) {
+        let size = self.size;
+        let mut spawn_pins = |widths: Vec<u8>, x: f32, direction: PinDirection| {
+            let count = widths.len();
+            widths
+                .into_iter()
+                .enumerate()
+                .map(|(idx, width)| {
+                    let pos = Vec2::new(x, pin_offset(idx, count, size.y));
+                    commands.spawn(NodeSpawner::from_pos(pos).width(width).direction(direction)).id()
+                })
+                .collect::<Vec<_>>()
+        };
+        let (input_widths, output_widths) = self.pin_widths();
+        let inputs = spawn_pins(input_widths, -size.x / 2.0, PinDirection::Sink);
+        let outputs = spawn_pins(output_widths, size.x / 2.0, PinDirection::Driver);
+
+        let font = self.text.text.sections[0].style.font.clone();
+        let label = |name: &str, idx: usize, count: usize, horizontal: HorizontalAlign| {
+            let side = if horizontal == HorizontalAlign::Left { -1.0 } else { 1.0 };
+            let x = side * (size.x / 2.0 - RADIUS - 5.0);
+
+            Text2dBundle {
+                text: Text::from_section(
+                    name,
+                    TextStyle { font: font.clone(), font_size: 20.0, color: Color::WHITE },
+                )
+                .with_alignment(TextAlignment { horizontal, vertical: VerticalAlign::Center }),
+                transform: Transform::from_xyz(x, pin_offset(idx, count, size.y), Depth::TEXT),
+                ..Default::default()
+            }
+        };
+        let (input_names, output_names) = self.pin_names();
+        let labels: Vec<_> = input_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| label(name, idx, inputs.len(), HorizontalAlign::Left))
+            .chain(output_names.iter().enumerate().map(|(idx, name)| {
+                label(name, idx, outputs.len(), HorizontalAlign::Right)
+            }))
+            .collect();
 
         let mut bund = commands.spawn((
             Gate {
                 inputs: inputs.clone(),
-                output,
+                outputs: outputs.clone(),
                 kind: self.kind,
+                width: self.width,
                 size: self.size,
+                state: self.state,
+                delay: self.delay,
             },
             self.shape,
         ));
 
+        if let Some(subcircuit) = self.subcircuit {
+            bund.insert(subcircuit);
+        }
+
         bund.push_children(&inputs)
-            .add_child(output)
+            .push_children(&outputs)
             .with_children(|b| {
-                b.spawn(self.text);
+                b.spawn((self.text, GateLabel));
+                for label in labels {
+                    b.spawn(label);
+                }
             });
 
-        bund
+        (bund.id(), inputs, outputs)
     }
 }
 
 #[derive(Component)]
 pub struct Gate {
     pub inputs: Vec<Entity>,
-    pub output: Entity,
+    pub outputs: Vec<Entity>,
     pub size: Vec2,
     pub kind: GateType,
+    /// Number of bits the gate works on
+    pub width: u8,
+    /// Stored bit of flip-flops and latches
+    pub state: GateState,
+    /// Delay in ticks, if it isn't the one of its type
+    pub delay: Option<u32>,
 }
 
-#[derive(Debug, Clone)]
-pub enum GateType {
-    And,
-    Or,
-    Xor,
-    Not,
-}
+impl Gate {
+    /// Computes the outputs from the values of the inputs, along with the new state of flip-flops.
+    /// Custom components settle their inlined circuit at once, so they take only their own delay, like any other gate.
+    /// Their outputs are unknown if it doesn't settle.
+    pub fn evaluate(&self, subcircuit: Option<&mut Subcircuit>, inputs: &[Signal]) -> (Vec<Signal>, GateState) {
+        let mut state = self.state;
+        let outputs = match subcircuit {
+            Some(subcircuit) => {
+                let circuit = &mut subcircuit.circuit;
+                for (&(_, pin), &value) in subcircuit.interface.inputs.iter().zip(inputs) {
+                    circuit.set_signal(pin, value);
+                }
+                let settled = circuit.settle(SETTLE_STEPS).is_ok();
+                let output = |&(_, pin): &(String, _)| match settled {
+                    true => circuit.signal(pin),
+                    false => Signal::unknown(circuit.width(pin)),
+                };
+                subcircuit.interface.outputs.iter().map(output).collect()
+            }
+            None => self.kind.update(inputs, self.width, &mut state),
+        };
+        (outputs, state)
+    }
 
-impl GateType {
-    pub fn as_str(&self) -> &'static str {
-        use GateType::*;
-        match self {
-            And => "And",
-            Or => "Or",
-            Xor => "Xor",
-            Not => "Not",
+    /// Everything needed to respawn the gate
+    pub fn record(&self, entity: Entity, pos: Vec2) -> GateRecord {
+        GateRecord {
+            gate: entity,
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            kind: self.kind,
+            width: self.width,
+            state: self.state,
+            pos,
+            size: self.size,
+            delay: self.delay,
         }
     }
+}
 
-    pub fn num_inputs(&self) -> usize {
-        use GateType::*;
-        match self {
-            And | Or | Xor => 2,
-            Not => 1,
-        }
+/// Everything needed to spawn gates, custom components included
+#[derive(SystemParam)]
+pub struct GateSpawner<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    pub asset_server: Res<'w, AssetServer>,
+    pub library: Res<'w, Library>,
+}
+
+impl<'w, 's> GateSpawner<'w, 's> {
+    pub fn bundle(&self, kind: GateType, size: Vec2) -> GateBundle {
+        GateBundle::new(&self.asset_server, &self.library, kind, size)
     }
 }
 
-/// Holds a reference to the currently moving gate, as well as the offset it was selected at
+/// The text showing the type of a gate
+#[derive(Component)]
+struct GateLabel;
+
+/// Holds a reference to the currently moving gate, as well as the offset it was selected at and whether it was just
+/// placed from the gate menu
+#[derive(Resource)]
+pub struct MovingGate(pub Option<(Entity, Vec2, bool)>);
+
+/// Holds a reference to the gate under the mouse
+#[derive(Resource)]
+pub struct HoveredGate(pub Option<Entity>);
+
+/// Gates picked with shift-click, to be turned into a component
 #[derive(Resource)]
-pub struct MovingGate(pub Option<(Entity, Vec2)>);
+pub struct Selection(pub Vec<Entity>);
 
-fn snap_vec(v: Vec2, grid_size: Vec2) -> Vec2 {
+/// Spacing of the grid gates are moved on
+pub const GRID_SIZE: Vec2 = Vec2::new(20.0, 20.0);
+
+pub fn snap_vec(v: Vec2, grid_size: Vec2) -> Vec2 {
     (v / grid_size).round() * grid_size
 }
 
+fn hover_gate(query: Query<(Entity, &Transform, &Gate)>, mut hovered: ResMut<HoveredGate>, cursor: Res<Cursor>) {
+    let p = cursor.0;
+    hovered.0 = query
+        .iter()
+        .find(|(_, transform, gate)| {
+            let pos = transform.translation.truncate();
+            p.cmpgt(pos - gate.size / 2.0).all() && p.cmplt(pos + gate.size / 2.0).all()
+        })
+        .map(|(entity, _, _)| entity);
+}
+
+/// Adds or removes the gate under the mouse from the selection with shift-click, and clears it with escape
+fn select_gate(
+    mut selection: ResMut<Selection>,
+    hovered: Res<HoveredGate>,
+    keys: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+) {
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if keys.just_pressed(KeyCode::Escape) && !selection.0.is_empty() {
+        selection.0.clear();
+    } else if shift && mouse_input.just_pressed(MouseButton::Left) {
+        let Some(entity) = hovered.0 else { return };
+        match selection.0.iter().position(|&selected| selected == entity) {
+            Some(idx) => {
+                selection.0.remove(idx);
+            }
+            None => selection.0.push(entity),
+        }
+    }
+}
+
+fn show_selection(
+    selection: Res<Selection>,
+    diagnostics: Res<Diagnostics>,
+    mut gates: Query<(Entity, &Gate, &mut DrawMode)>,
+) {
+    if !selection.is_changed() && !diagnostics.is_changed() {
+        return;
+    }
+
+    for (entity, gate, mut mode) in &mut gates {
+        let color = if diagnostics.flags_gate(entity, gate) { Colors::WARNING } else { GATE_COLOR };
+        let color = match selection.0.contains(&entity) {
+            true => color + Color::WHITE * 0.2,
+            false => color,
+        };
+        *mode = DrawMode::Fill(FillMode::color(color));
+    }
+}
+
 fn move_gate(
     mut query: Query<(Entity, &mut Transform, &Gate)>,
     mut selected: ResMut<MovingGate>,
+    mut history: ResMut<History>,
+    // Where the moving gate was picked up
+    mut start: Local<Option<Vec2>>,
     cursor: Res<Cursor>,
+    keys: Res<Input<KeyCode>>,
     mouse_input: Res<Input<MouseButton>>,
 ) {
-    if mouse_input.just_pressed(MouseButton::Left) {
+    // Shift-click selects gates instead
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if mouse_input.just_pressed(MouseButton::Left) && !shift {
         for (entity, transform, gate) in query.iter() {
             let p = cursor.0;
             let pos = transform.translation.truncate();
             let size = gate.size;
 
             if p.cmpgt(pos - size / 2.0).all() && p.cmplt(pos + size / 2.0).all() {
-                selected.0 = Some((entity, pos - p));
+                selected.0 = Some((entity, pos - p, false));
                 break;
             }
         }
     } else if mouse_input.just_released(MouseButton::Left) {
-        selected.0 = None;
+        if let (Some((entity, _, placed)), Some(from)) = (selected.0.take(), start.take()) {
+            let Ok(( _, transform, _ )) = query.get(entity) else { return };
+            let to = transform.translation.truncate();
+
+            if placed {
+                history.drop_placed(entity, to);
+            } else if from != to {
+                history.push(Edit::MoveGate { gate: entity, from, to });
+            }
+        }
     }
 
-    if let Some((entity, offset)) = selected.0 {
+    if let Some((entity, offset, _)) = selected.0 {
         let Ok(( _, mut transform, _ )) = query.get_mut(entity) else { return };
+        start.get_or_insert(transform.translation.truncate());
+
         *transform = transform.with_translation(
-            snap_vec(cursor.0 + offset, Vec2::new(20.0, 20.0)).extend(Depth::GATE),
+            snap_vec(cursor.0 + offset, GRID_SIZE).extend(Depth::GATE),
         );
     }
 }
 
-fn process_gates(gates: Query<&Gate>, mut nodes: Query<&mut Node>) {
-    for gate in gates.iter() {
-        let get_node = |id: Entity| nodes.get(id).unwrap();
+/// Deletes the moving gate, or the one under the mouse, along with its nodes and edges
+fn delete_gate(
+    mut commands: Commands,
+    gates: Query<(&Gate, &Transform)>,
+    edges: Query<(Entity, &Edge, &EdgeSignal)>,
+    hovered: Res<HoveredGate>,
+    mut moving: ResMut<MovingGate>,
+    mut history: ResMut<History>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::Delete) {
+        return;
+    }
+
+    let Some(entity) = moving.0.map(|(entity, _, _)| entity).or(hovered.0) else { return };
+    let Ok((gate, transform)) = gates.get(entity) else { return };
+
+    // Edges are removed right away instead of waiting for `cleanup_edges` to notice the missing nodes next frame
+    let is_pin = |node: &Entity| gate.outputs.contains(node) || gate.inputs.contains(node);
+    let attached: Vec<_> = edges
+        .iter()
+        .filter(|(_, edge, _)| is_pin(&edge.from) || is_pin(&edge.to))
+        .map(|(edge, &Edge { from, to }, signal)| EdgeRecord { edge, from, to, delay: signal.delay })
+        .collect();
+
+    for edge in &attached {
+        commands.entity(edge.edge).despawn();
+    }
+    commands.entity(entity).despawn_recursive();
+    moving.0 = None;
+
+    history.push(Edit::RemoveGate {
+        gate: gate.record(entity, transform.translation.truncate()),
+        edges: attached,
+    });
+}
 
-        use GateType::*;
-        let output = match gate.kind {
-            And => get_node(gate.inputs[0]).0 & get_node(gate.inputs[1]).0,
-            Or => get_node(gate.inputs[0]).0 | get_node(gate.inputs[1]).0,
-            Xor => get_node(gate.inputs[0]).0 ^ get_node(gate.inputs[1]).0,
-            Not => !get_node(gate.inputs[0]).0,
+/// Adds or removes an input of the gate under the mouse with the + and - keys, and changes its width with the up and down arrows.
+/// The gate is respawned, keeping the edges of the nodes that still exist and have the same width.
+fn edit_gate(
+    mut spawner: GateSpawner,
+    gates: Query<(&Gate, &Transform)>,
+    edges: Query<(Entity, &Edge, &EdgeSignal)>,
+    nodes: Query<&Node>,
+    hovered: Res<HoveredGate>,
+    mut history: ResMut<History>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let (input_delta, width_delta): (isize, i8) =
+        if keys.any_just_pressed([KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]) {
+            (1, 0)
+        } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+            (-1, 0)
+        } else if keys.just_pressed(KeyCode::Up) {
+            (0, 1)
+        } else if keys.just_pressed(KeyCode::Down) {
+            (0, -1)
+        } else {
+            return;
         };
 
-        nodes.get_mut(gate.output).unwrap().0 = output;
+    let Some(entity) = hovered.0 else { return };
+    let Ok((gate, transform)) = gates.get(entity) else { return };
+    // The pins of custom components come from their definition
+    if let GateType::Custom(_) = gate.kind {
+        return;
+    }
+
+    let width = gate.width.saturating_add_signed(width_delta);
+    let num_inputs = match width_delta {
+        0 => gate.inputs.len().saturating_add_signed(input_delta),
+        // Mergers have one input per bit
+        _ if !gate.kind.input_range(width).contains(&gate.inputs.len()) => gate.kind.default_inputs(width),
+        _ => gate.inputs.len(),
+    };
+    if (num_inputs, width) == (gate.inputs.len(), gate.width)
+        || !gate.kind.width_range().contains(&width)
+        || !gate.kind.input_range(width).contains(&num_inputs)
+    {
+        return;
+    }
+
+    let pos = transform.translation.truncate();
+    let old = gate.record(entity, pos);
+
+    let is_pin = |node: &Entity| gate.outputs.contains(node) || gate.inputs.contains(node);
+    let attached: Vec<_> = edges
+        .iter()
+        .filter(|(_, edge, _)| is_pin(&edge.from) || is_pin(&edge.to))
+        .map(|(edge, &Edge { from, to }, signal)| EdgeRecord { edge, from, to, delay: signal.delay })
+        .collect();
+
+    for edge in &attached {
+        spawner.commands.entity(edge.edge).despawn();
+    }
+    spawner.commands.entity(entity).despawn_recursive();
+
+    let bundle = spawner
+        .bundle(gate.kind, Vec2::new(gate.size.x, GATE_SIZE.y))
+        .width(width)
+        .inputs(num_inputs)
+        .state(gate.state)
+        .delay(gate.delay)
+        .pos(pos);
+    let size = bundle.size;
+    let input_widths = gate.kind.input_widths(num_inputs, width);
+    let output_widths = gate.kind.output_widths(width);
+    let (new_gate, inputs, outputs) = bundle.spawn(&mut spawner.commands);
+
+    // Follow the edges to the nodes of the new gate, along with their width,
+    // dropping those of removed pins
+    let remap = |node: Entity| {
+        if let Some(idx) = gate.outputs.iter().position(|&output| output == node) {
+            Some((*outputs.get(idx)?, output_widths[idx]))
+        } else if let Some(idx) = gate.inputs.iter().position(|&input| input == node) {
+            Some((*inputs.get(idx)?, input_widths[idx]))
+        } else {
+            Some((node, nodes.get(node).ok()?.width))
+        }
+    };
+
+    let mut edits = vec![
+        Edit::RemoveGate { gate: old, edges: attached.clone() },
+        Edit::AddGate(GateRecord {
+            gate: new_gate,
+            inputs: inputs.clone(),
+            outputs: outputs.clone(),
+            kind: gate.kind,
+            width,
+            state: gate.state,
+            pos,
+            size,
+            delay: gate.delay,
+        }),
+    ];
+    for edge in &attached {
+        let (Some((from, from_width)), Some((to, to_width))) = (remap(edge.from), remap(edge.to)) else { continue };
+        if from_width != to_width {
+            continue;
+        }
+
+        let delay = edge.delay;
+        let edge = spawner.commands.spawn(EdgeBundle::new(from, to).delay(delay)).id();
+        edits.push(Edit::AddEdge(EdgeRecord { edge, from, to, delay }));
+    }
+
+    history.push(Edit::Batch(edits));
+}
+
+/// Changes the clock under the mouse: + and - change its period, [ and ] how long it stays high,
+/// and space pauses or resumes it
+fn configure_clock(
+    mut gates: Query<&mut Gate>,
+    hovered: Res<HoveredGate>,
+    mut history: ResMut<History>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let Some(entity) = hovered.0 else { return };
+    let Ok(mut gate) = gates.get_mut(entity) else { return };
+    let GateType::Clock(config) = gate.kind else { return };
+    let paused = gate.state.paused;
+
+    if keys.just_pressed(KeyCode::Space) {
+        history.push(Edit::ConfigureGate { gate: entity, from: (gate.kind, paused), to: (gate.kind, !paused) });
+        gate.state.paused = !paused;
+        return;
+    }
+
+    let pressed = |codes: &[KeyCode]| keys.any_just_pressed(codes.iter().copied());
+    let ClockConfig { mut period, mut high } = config;
+    if pressed(&[KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]) {
+        period += 1;
+    } else if pressed(&[KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        period = period.saturating_sub(1).max(1);
+    } else if pressed(&[KeyCode::RBracket]) {
+        high += 1;
+    } else if pressed(&[KeyCode::LBracket]) {
+        high = high.saturating_sub(1);
+    } else {
+        return;
+    }
+
+    let new = GateType::Clock(ClockConfig { period, high: high.min(period) });
+    if new != gate.kind {
+        history.push(Edit::ConfigureGate { gate: entity, from: (gate.kind, paused), to: (new, paused) });
+        gate.kind = new;
+        gate.state.ticks %= period;
+    }
+}
+
+/// Shows how far each clock is into its period
+fn show_clock_phase(gates: Query<&Gate, Changed<Gate>>, mut labels: Query<(&Parent, &mut Text), With<GateLabel>>) {
+    for (parent, mut text) in &mut labels {
+        let Ok(gate) = gates.get(parent.get()) else { continue };
+        let GateType::Clock(config) = gate.kind else { continue };
+
+        text.sections[0].value = if gate.state.paused {
+            "Clock\npaused".to_string()
+        } else {
+            format!("Clock\n{}/{}", gate.state.ticks + 1, config.period)
+        };
     }
 }
@@ -0,0 +1,420 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    circuit::{GateState, GateType, Signal},
+    component::Library,
+    constants::Depth,
+    edge::{EdgeBundle, EdgeSignal},
+    gate::{Gate, GateBundle},
+    node::Node,
+    ui::{spawn_panel_node, Panel, PanelRootMarker, RemoveNodeMarker},
+};
+
+pub struct HistoryPlugin {
+    /// Maximum number of edits that can be undone
+    pub depth: usize,
+}
+
+impl Default for HistoryPlugin {
+    fn default() -> Self {
+        Self { depth: 100 }
+    }
+}
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(History::new(self.depth))
+            .add_system(undo_redo);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GateRecord {
+    pub gate: Entity,
+    pub inputs: Vec<Entity>,
+    pub outputs: Vec<Entity>,
+    pub kind: GateType,
+    pub width: u8,
+    pub state: GateState,
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub delay: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EdgeRecord {
+    pub edge: Entity,
+    pub from: Entity,
+    pub to: Entity,
+    pub delay: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PanelNodeRecord {
+    pub panel: Panel,
+    pub node: Entity,
+    /// Position in the panel
+    pub index: usize,
+    pub label: String,
+    pub value: Signal,
+    pub width: u8,
+    /// Edges that were attached to the node, and need to come back with it
+    pub edges: Vec<EdgeRecord>,
+}
+
+/// An edit of the circuit, with everything needed to undo and redo it
+#[derive(Debug, Clone)]
+pub enum Edit {
+    AddGate(GateRecord),
+    /// The edges attached to the gate are removed along with it
+    RemoveGate { gate: GateRecord, edges: Vec<EdgeRecord> },
+    MoveGate { gate: Entity, from: Vec2, to: Vec2 },
+    AddEdge(EdgeRecord),
+    RemoveEdge(EdgeRecord),
+    AddPanelNode(PanelNodeRecord),
+    RemovePanelNode(PanelNodeRecord),
+    /// Changes the value of a node by hand
+    SetNode { node: Entity, from: Signal, to: Signal },
+    /// Changes the number of bits of a panel node
+    ResizeNode { node: Entity, from: u8, to: u8 },
+    /// Changes the settings of a gate, like the period of a clock or whether it is paused
+    ConfigureGate { gate: Entity, from: (GateType, bool), to: (GateType, bool) },
+    /// Changes the delay of a gate or an edge, in ticks
+    SetDelay { target: Entity, from: Option<u32>, to: Option<u32> },
+    /// Several edits made at once, undone in reverse order
+    Batch(Vec<Edit>),
+}
+
+impl Edit {
+    fn remap(&mut self, remaps: &[(Entity, Entity)]) {
+        for entity in self.entities_mut() {
+            if let Some(&(_, new)) = remaps.iter().find(|(old, _)| old == entity) {
+                *entity = new;
+            }
+        }
+    }
+
+    fn entities_mut(&mut self) -> Vec<&mut Entity> {
+        fn edge(e: &mut EdgeRecord) -> [&mut Entity; 3] {
+            [&mut e.edge, &mut e.from, &mut e.to]
+        }
+
+        fn gate(g: &mut GateRecord) -> Vec<&mut Entity> {
+            let mut entities = vec![&mut g.gate];
+            entities.extend(g.inputs.iter_mut());
+            entities.extend(g.outputs.iter_mut());
+            entities
+        }
+
+        match self {
+            Edit::AddGate(g) => gate(g),
+            Edit::RemoveGate { gate: g, edges } => {
+                let mut entities = gate(g);
+                entities.extend(edges.iter_mut().flat_map(edge));
+                entities
+            }
+            Edit::MoveGate { gate, .. } | Edit::ConfigureGate { gate, .. } => vec![gate],
+            Edit::SetDelay { target, .. } => vec![target],
+            Edit::AddEdge(e) | Edit::RemoveEdge(e) => edge(e).into(),
+            Edit::AddPanelNode(i) | Edit::RemovePanelNode(i) => {
+                let mut entities = vec![&mut i.node];
+                entities.extend(i.edges.iter_mut().flat_map(edge));
+                entities
+            }
+            Edit::SetNode { node, .. } | Edit::ResizeNode { node, .. } => vec![node],
+            Edit::Batch(edits) => edits.iter_mut().flat_map(Edit::entities_mut).collect(),
+        }
+    }
+}
+
+/// Undo and redo stacks of the edits made to the circuit
+#[derive(Resource)]
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    pub depth: usize,
+}
+
+impl History {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            depth,
+        }
+    }
+
+    pub fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.undo.push_back(edit);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Dropping a freshly created gate is part of creating it, so it moves the gate of the last edit instead
+    pub fn drop_placed(&mut self, gate: Entity, pos: Vec2) {
+        if let Some(Edit::AddGate(record)) = self.undo.back_mut() {
+            if record.gate == gate {
+                record.pos = pos;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Entities get a new id when they are respawned, so every edit referring to the old one must follow
+    fn remap(&mut self, remaps: &[(Entity, Entity)]) {
+        for edit in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            edit.remap(remaps);
+        }
+    }
+}
+
+/// Everything needed to apply edits to the world
+#[derive(SystemParam)]
+struct Editor<'w, 's> {
+    commands: Commands<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    library: Res<'w, Library>,
+    gates: Query<'w, 's, (&'static mut Transform, &'static mut Gate)>,
+    nodes: Query<'w, 's, &'static mut Node>,
+    signals: Query<'w, 's, &'static mut EdgeSignal>,
+    roots: Query<'w, 's, (Entity, &'static PanelRootMarker)>,
+    buttons: Query<'w, 's, (Entity, &'static RemoveNodeMarker)>,
+}
+
+impl<'w, 's> Editor<'w, 's> {
+    fn spawn_gate(&mut self, record: &GateRecord) -> Vec<(Entity, Entity)> {
+        let (gate, inputs, outputs) = GateBundle::new(&self.asset_server, &self.library, record.kind, record.size)
+            .width(record.width)
+            .inputs(record.inputs.len())
+            .state(record.state)
+            .delay(record.delay)
+            .pos(record.pos)
+            .spawn(&mut self.commands);
+
+        let mut remaps = vec![(record.gate, gate)];
+        remaps.extend(record.inputs.iter().copied().zip(inputs));
+        remaps.extend(record.outputs.iter().copied().zip(outputs));
+        remaps
+    }
+
+    fn spawn_edge(&mut self, record: &EdgeRecord) -> Vec<(Entity, Entity)> {
+        let edge = self.commands.spawn(EdgeBundle::new(record.from, record.to).delay(record.delay)).id();
+        vec![(record.edge, edge)]
+    }
+
+    fn spawn_panel_node(&mut self, record: &PanelNodeRecord) -> Vec<(Entity, Entity)> {
+        let (root, _) = self.roots.iter().find(|(_, root)| root.0 == record.panel).unwrap();
+        let node = spawn_panel_node(
+            &mut self.commands,
+            record.panel,
+            root,
+            &self.asset_server,
+            Some(record.index),
+            record.label.clone(),
+            Node { value: record.value, width: record.width },
+        );
+
+        let mut remaps = vec![(record.node, node)];
+        self.spawn_attached_edges(&record.edges, &mut remaps);
+        remaps
+    }
+
+    /// Respawns edges that were removed along with a node or a gate, which was just respawned with new ids
+    fn spawn_attached_edges(&mut self, edges: &[EdgeRecord], remaps: &mut Vec<(Entity, Entity)>) {
+        let remap = |entity: Entity| {
+            remaps.iter().find(|(old, _)| *old == entity).map_or(entity, |&(_, new)| new)
+        };
+
+        let new: Vec<_> = edges
+            .iter()
+            .map(|edge| {
+                let bundle = EdgeBundle::new(remap(edge.from), remap(edge.to)).delay(edge.delay);
+                let new = self.commands.spawn(bundle).id();
+                (edge.edge, new)
+            })
+            .collect();
+        remaps.extend(new);
+    }
+
+    fn despawn_gate(&mut self, record: &GateRecord, edges: &[EdgeRecord]) {
+        for edge in edges {
+            self.despawn(edge.edge);
+        }
+        self.despawn(record.gate);
+    }
+
+    fn despawn(&mut self, entity: Entity) {
+        if let Some(entity) = self.commands.get_entity(entity) {
+            entity.despawn_recursive();
+        }
+    }
+
+    fn despawn_panel_node(&mut self, node: Entity) {
+        if let Some((button, _)) = self.buttons.iter().find(|(_, marker)| marker.0 == node) {
+            self.despawn(button);
+        }
+        self.despawn(node);
+    }
+
+    fn move_gate(&mut self, gate: Entity, pos: Vec2) {
+        if let Ok((mut transform, _)) = self.gates.get_mut(gate) {
+            transform.translation = pos.extend(Depth::GATE);
+        }
+    }
+
+    fn configure_gate(&mut self, gate: Entity, (kind, paused): (GateType, bool)) {
+        if let Ok((_, mut gate)) = self.gates.get_mut(gate) {
+            gate.kind = kind;
+            gate.state.paused = paused;
+            if let GateType::Clock(config) = kind {
+                gate.state.ticks %= config.period;
+            }
+        }
+    }
+
+    fn set_delay(&mut self, target: Entity, delay: Option<u32>) {
+        if let Ok((_, mut gate)) = self.gates.get_mut(target) {
+            gate.delay = delay;
+        } else if let Ok(mut signal) = self.signals.get_mut(target) {
+            signal.delay = delay;
+        }
+    }
+
+    fn set_node(&mut self, node: Entity, value: Signal) {
+        if let Ok(mut node) = self.nodes.get_mut(node) {
+            node.value = value;
+        }
+    }
+
+    fn resize_node(&mut self, node: Entity, width: u8) {
+        if let Ok(mut node) = self.nodes.get_mut(node) {
+            node.width = width;
+            node.value = node.value.mask(width);
+        }
+    }
+
+    /// Reverts an edit, and returns the entities that had to be respawned
+    fn undo(&mut self, edit: &Edit) -> Vec<(Entity, Entity)> {
+        match edit {
+            Edit::AddGate(record) => self.despawn(record.gate),
+            Edit::RemoveGate { gate, edges } => {
+                let mut remaps = self.spawn_gate(gate);
+                self.spawn_attached_edges(edges, &mut remaps);
+                return remaps;
+            }
+            &Edit::MoveGate { gate, from, .. } => self.move_gate(gate, from),
+            &Edit::ConfigureGate { gate, from, .. } => self.configure_gate(gate, from),
+            &Edit::SetDelay { target, from, .. } => self.set_delay(target, from),
+            Edit::AddEdge(record) => self.despawn(record.edge),
+            Edit::RemoveEdge(record) => return self.spawn_edge(record),
+            Edit::AddPanelNode(record) => self.despawn_panel_node(record.node),
+            Edit::RemovePanelNode(record) => return self.spawn_panel_node(record),
+            &Edit::SetNode { node, from, .. } => self.set_node(node, from),
+            &Edit::ResizeNode { node, from, .. } => self.resize_node(node, from),
+            Edit::Batch(edits) => {
+                let mut edits = edits.clone();
+                let mut remaps = vec![];
+                while let Some(edit) = edits.pop() {
+                    let new = self.undo(&edit);
+                    // The edits left to undo may refer to what was just respawned
+                    edits.iter_mut().for_each(|edit| edit.remap(&new));
+                    remaps.extend(new);
+                }
+                return remaps;
+            }
+        }
+        vec![]
+    }
+
+    /// Applies an edit again, and returns the entities that had to be respawned
+    fn redo(&mut self, edit: &Edit) -> Vec<(Entity, Entity)> {
+        match edit {
+            Edit::AddGate(record) => return self.spawn_gate(record),
+            Edit::RemoveGate { gate, edges } => self.despawn_gate(gate, edges),
+            &Edit::MoveGate { gate, to, .. } => self.move_gate(gate, to),
+            &Edit::ConfigureGate { gate, to, .. } => self.configure_gate(gate, to),
+            &Edit::SetDelay { target, to, .. } => self.set_delay(target, to),
+            Edit::AddEdge(record) => return self.spawn_edge(record),
+            Edit::RemoveEdge(record) => self.despawn(record.edge),
+            Edit::AddPanelNode(record) => return self.spawn_panel_node(record),
+            Edit::RemovePanelNode(record) => self.despawn_panel_node(record.node),
+            &Edit::SetNode { node, to, .. } => self.set_node(node, to),
+            &Edit::ResizeNode { node, to, .. } => self.resize_node(node, to),
+            Edit::Batch(edits) => {
+                let mut edits = edits.clone();
+                let mut remaps = vec![];
+                for idx in 0..edits.len() {
+                    let new = self.redo(&edits[idx]);
+                    edits[idx + 1..].iter_mut().for_each(|edit| edit.remap(&new));
+                    remaps.extend(new);
+                }
+                return remaps;
+            }
+        }
+        vec![]
+    }
+}
+
+fn undo_redo(mut history: ResMut<History>, mut editor: Editor, keys: Res<Input<KeyCode>>) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if !ctrl || !keys.just_pressed(KeyCode::Z) {
+        return;
+    }
+
+    let remaps = if shift {
+        let Some(edit) = history.redo.pop() else { return };
+        let remaps = editor.redo(&edit);
+        history.undo.push_back(edit);
+        remaps
+    } else {
+        let Some(edit) = history.undo.pop_back() else { return };
+        let remaps = editor.undo(&edit);
+        history.redo.push(edit);
+        remaps
+    };
+
+    history.remap(&remaps);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(gate: Entity, pos: Vec2) -> GateRecord {
+        GateRecord {
+            gate,
+            inputs: vec![],
+            outputs: vec![],
+            kind: GateType::Not,
+            width: 1,
+            state: GateState::default(),
+            pos,
+            size: Vec2::ONE,
+            delay: None,
+        }
+    }
+
+    #[test]
+    fn only_the_first_drop_is_part_of_placing() {
+        let (gate, placed, dropped, moved) = (Entity::from_raw(0), Vec2::ZERO, Vec2::X, Vec2::Y);
+        let mut history = History::new(10);
+        history.push(Edit::AddGate(record(gate, placed)));
+        history.drop_placed(gate, dropped);
+        history.push(Edit::MoveGate { gate, from: dropped, to: moved });
+
+        let Some(Edit::MoveGate { from, .. }) = history.undo.pop_back() else { panic!() };
+        assert_eq!(from, dropped);
+        let Some(Edit::AddGate(record)) = history.undo.pop_back() else { panic!() };
+        assert_eq!(record.pos, dropped);
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use logic_sim::expression::Expr;
+
+use crate::{
+    constants::Colors,
+    file::CircuitSnapshot,
+    node::HoveredNode,
+    ui::{text_builder, NodeLabel, OutputNodeMarker},
+};
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(create_inspector_ui).add_system(inspect_output);
+    }
+}
+
+/// Expression of the last output node hovered
+#[derive(Component)]
+struct InspectorText;
+
+fn create_inspector_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(75.0),
+                    bottom: Val::Px(70.0),
+                    ..default()
+                },
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: Colors::UI_BG.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            c.spawn((text_builder("Hover an output to see its expression", &asset_server), InspectorText));
+        });
+}
+
+/// Reads back the expression of an output node when it gets hovered, as drawn and simplified, and keeps it shown
+/// until another one is hovered. Hovering the same one again reads it again, in case the circuit changed.
+fn inspect_output(
+    hovered: Res<HoveredNode>,
+    // Output under the mouse, to read its expression once per hover
+    mut inspected: Local<Option<Entity>>,
+    outputs: Query<&NodeLabel, With<OutputNodeMarker>>,
+    mut text: Query<&mut Text, With<InspectorText>>,
+    snapshot: CircuitSnapshot,
+) {
+    let Some((node, NodeLabel(label))) = hovered.0.and_then(|node| Some((node, outputs.get(node).ok()?))) else {
+        *inspected = None;
+        return;
+    };
+    if *inspected == Some(node) {
+        return;
+    }
+    *inspected = Some(node);
+
+    let (circuit, interface) = snapshot.to_file().to_circuit();
+    let Some(&(_, pin)) = interface.outputs.iter().find(|(name, _)| name == label) else { return };
+    text.single_mut().sections[0].value = match Expr::of_pin(&circuit, &interface, pin) {
+        Ok(expr) => format!("{label} = {expr}\nSimplified: {}", expr.simplify()),
+        Err(e) => format!("{label} has no expression: {e}"),
+    };
+}
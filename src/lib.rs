@@ -0,0 +1,8 @@
+//! Parts of the simulator that don't depend on Bevy, usable from plain Rust code and tests.
+
+pub mod circuit;
+pub mod equivalence;
+pub mod expression;
+pub mod minimize;
+pub mod save;
+pub mod truth_table;
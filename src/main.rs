@@ -1,18 +1,40 @@
+mod camera;
+mod compare;
+mod component;
 mod constants;
 mod cursor;
+mod diagnostics;
 mod node;
 mod edge;
+mod file;
 mod gate;
+mod history;
+mod inspector;
+mod simulation;
+mod synthesis;
+mod table;
 mod ui;
 
+use logic_sim::circuit;
+
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
+use camera::CameraPlugin;
+use compare::ComparePlugin;
+use component::ComponentPlugin;
 use constants::Colors;
 use cursor::CursorPlugin;
+use diagnostics::DiagnosticsPlugin;
 
 use gate::GatePlugin;
-use node::{NodePlugin, NodeSpawner};
+use node::NodePlugin;
 use edge::EdgePlugin;
+use file::FilePlugin;
+use history::HistoryPlugin;
+use inspector::InspectorPlugin;
+use simulation::SimulationPlugin;
+use synthesis::SynthesisPlugin;
+use table::TablePlugin;
 use ui::UiBuilder;
 
 fn startup(mut commands: Commands, _asset_server: Res<AssetServer>) {
@@ -25,11 +47,21 @@ fn main() {
         .insert_resource(ClearColor(Colors::BG))
         .add_plugins(DefaultPlugins)
         .add_plugin(CursorPlugin)
+        .add_plugin(CameraPlugin)
         .add_plugin(ShapePlugin)
         .add_plugin(EdgePlugin)
         .add_plugin(NodePlugin)
         .add_plugin(GatePlugin)
         .add_plugin(UiBuilder)
+        .add_plugin(FilePlugin)
+        .add_plugin(ComponentPlugin)
+        .add_plugin(SimulationPlugin)
+        .add_plugin(DiagnosticsPlugin)
+        .add_plugin(TablePlugin)
+        .add_plugin(SynthesisPlugin)
+        .add_plugin(InspectorPlugin)
+        .add_plugin(ComparePlugin)
+        .add_plugin(HistoryPlugin::default())
         .add_startup_system(startup)
         .run();
 }
@@ -0,0 +1,315 @@
+//! Minimal sums of products of truth tables, found with the Quine–McCluskey method, and Karnaugh maps to read them
+//! from.
+//!
+//! Rows are numbered like in [`TruthTable`], with the first input in the highest bit. Outputs that are unknown or
+//! floating in a row are free to take either value there.
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    circuit::Logic,
+    expression::Expr,
+    truth_table::{Column, TruthTable},
+};
+
+/// Number of variables a Karnaugh map can show
+pub const KARNAUGH_VARIABLES: usize = 6;
+
+/// Number of branches tried when looking for the smallest set of implicants, after which the one covering the most
+/// rows is taken each time
+const COVER_BUDGET: usize = 10_000;
+
+/// Product of some of the variables, each one inverted or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Implicant {
+    /// Variables appearing in the product
+    care: u64,
+    /// Value of the variables appearing in the product, the others are zero
+    value: u64,
+}
+
+impl Implicant {
+    fn covers(&self, row: u64) -> bool {
+        row & self.care == self.value
+    }
+
+    fn literals(&self) -> u32 {
+        self.care.count_ones()
+    }
+
+    /// Implicant covering both, if they differ by a single variable
+    fn merge(&self, other: &Implicant) -> Option<Implicant> {
+        let diff = self.value ^ other.value;
+        (self.care == other.care && diff.count_ones() == 1)
+            .then_some(Implicant { care: self.care & !diff, value: self.value & !diff })
+    }
+
+    /// Product of the variables, the first one in the highest bit
+    fn to_expr(self, variables: &[String]) -> Expr {
+        let bits = variables.len();
+        let mut literals: Vec<_> = (0..bits)
+            .filter(|idx| self.care >> (bits - 1 - idx) & 1 == 1)
+            .map(|idx| {
+                let var = Expr::Var(variables[idx].clone());
+                match self.value >> (bits - 1 - idx) & 1 {
+                    1 => var,
+                    _ => Expr::Not(Box::new(var)),
+                }
+            })
+            .collect();
+        match literals.len() {
+            0 => Expr::Const(true),
+            1 => literals.pop().unwrap(),
+            _ => Expr::And(literals),
+        }
+    }
+}
+
+/// Smallest sum of products that is true on `minterms`, false on the other rows, and either on `dont_cares`
+pub fn minimal_sum(variables: &[String], minterms: &[u64], dont_cares: &[u64]) -> Expr {
+    let primes = prime_implicants(variables.len(), minterms.iter().chain(dont_cares));
+    let mut products: Vec<_> = cover(&primes, minterms).into_iter().map(|prime| prime.to_expr(variables)).collect();
+    match products.len() {
+        0 => Expr::Const(false),
+        1 => products.pop().unwrap(),
+        _ => Expr::Or(products),
+    }
+}
+
+/// Products that can't lose a variable without covering a row outside of `rows`
+fn prime_implicants<'a>(bits: usize, rows: impl Iterator<Item = &'a u64>) -> Vec<Implicant> {
+    let all = if bits == 64 { u64::MAX } else { (1 << bits) - 1 };
+    let mut current: BTreeSet<_> = rows.map(|&row| Implicant { care: all, value: row }).collect();
+    let mut primes = vec![];
+
+    while !current.is_empty() {
+        let implicants: Vec<_> = current.iter().copied().collect();
+        let mut merged = vec![false; implicants.len()];
+        let mut next = BTreeSet::new();
+        for (i, a) in implicants.iter().enumerate() {
+            for (j, b) in implicants.iter().enumerate().skip(i + 1) {
+                if let Some(implicant) = a.merge(b) {
+                    next.insert(implicant);
+                    merged[i] = true;
+                    merged[j] = true;
+                }
+            }
+        }
+        primes.extend(implicants.iter().zip(&merged).filter(|&(_, &merged)| !merged).map(|(&prime, _)| prime));
+        current = next;
+    }
+    primes
+}
+
+/// Fewest primes covering every minterm, with the fewest literals among those
+fn cover(primes: &[Implicant], minterms: &[u64]) -> Vec<Implicant> {
+    let mut best = None;
+    let mut budget = COVER_BUDGET;
+    search(primes, minterms, &mut vec![], &mut best, &mut budget);
+    // Products with the first variables come first
+    let mut best = best.unwrap_or_default();
+    best.sort_by_key(|prime| (std::cmp::Reverse(prime.care), prime.value));
+    best
+}
+
+fn cost(implicants: &[Implicant]) -> (usize, u32) {
+    (implicants.len(), implicants.iter().map(Implicant::literals).sum())
+}
+
+/// Branches on the primes covering the row covered by the fewest of them, which is a single one for essential primes
+fn search(
+    primes: &[Implicant],
+    uncovered: &[u64],
+    chosen: &mut Vec<Implicant>,
+    best: &mut Option<Vec<Implicant>>,
+    budget: &mut usize,
+) {
+    if best.as_ref().is_some_and(|best| cost(chosen) >= cost(best)) {
+        return;
+    }
+    let Some(row) = uncovered.iter().min_by_key(|&&row| primes.iter().filter(|prime| prime.covers(row)).count())
+    else {
+        *best = Some(chosen.clone());
+        return;
+    };
+
+    let mut candidates: Vec<_> = primes.iter().filter(|prime| prime.covers(*row)).collect();
+    let covered = |prime: &Implicant| uncovered.iter().filter(|&&row| prime.covers(row)).count();
+    candidates.sort_by_key(|prime| (std::cmp::Reverse(covered(prime)), prime.literals()));
+    for prime in candidates {
+        if *budget == 0 && best.is_some() {
+            return;
+        }
+        *budget = budget.saturating_sub(1);
+
+        let rest: Vec<_> = uncovered.iter().copied().filter(|&row| !prime.covers(row)).collect();
+        chosen.push(*prime);
+        search(primes, &rest, chosen, best, budget);
+        chosen.pop();
+    }
+}
+
+/// Why a truth table can't be minimized or mapped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MinimizeError {
+    /// Inputs and outputs have to be single bits, this one is a bus
+    Bus(String),
+    /// A Karnaugh map can't show that many variables
+    TooManyVariables(usize),
+}
+
+impl fmt::Display for MinimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MinimizeError::Bus(name) => write!(f, "{name} has several bits"),
+            MinimizeError::TooManyVariables(count) => {
+                write!(f, "Karnaugh maps show up to {KARNAUGH_VARIABLES} variables, not {count}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MinimizeError {}
+
+/// Checks that the inputs and the given output are single bits, and returns the names of the inputs
+fn single_bits(table: &TruthTable, output: usize) -> Result<Vec<String>, MinimizeError> {
+    let columns = table.inputs.iter().chain([&table.outputs[output]]);
+    if let Some(Column { name, .. }) = columns.clone().find(|column| column.width != 1) {
+        return Err(MinimizeError::Bus(name.clone()));
+    }
+    Ok(table.inputs.iter().map(|column| column.name.clone()).collect())
+}
+
+impl TruthTable {
+    /// Smallest sum of products giving the `output`-th output, free where it is unknown or floating. Gates built from
+    /// it drive the output in every row, so it only stands for circuits whose outputs are always known.
+    pub fn minimize(&self, output: usize) -> Result<Expr, MinimizeError> {
+        let variables = single_bits(self, output)?;
+        let (mut minterms, mut dont_cares) = (vec![], vec![]);
+        for (row, values) in self.rows.iter().enumerate() {
+            match values.outputs[output].bit(0) {
+                Logic::High => minterms.push(row as u64),
+                Logic::Low => {}
+                Logic::Z | Logic::X => dont_cares.push(row as u64),
+            }
+        }
+        Ok(minimal_sum(&variables, &minterms, &dont_cares))
+    }
+}
+
+/// Values of an output laid out so that neighbouring cells, wrapping around the edges, differ by a single input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KarnaughMap {
+    /// The first half of the inputs, rounded down, sets the row and the rest the column
+    pub row_variables: Vec<String>,
+    pub column_variables: Vec<String>,
+    /// Values of the variables of each row and column, in Gray code order
+    pub row_values: Vec<u64>,
+    pub column_values: Vec<u64>,
+    pub cells: Vec<Vec<Logic>>,
+}
+
+/// Numbers of `bits` bits, each one differing from the previous one by a single bit
+fn gray_code(bits: usize) -> Vec<u64> {
+    (0..1u64 << bits).map(|idx| idx ^ (idx >> 1)).collect()
+}
+
+impl KarnaughMap {
+    pub fn new(table: &TruthTable, output: usize) -> Result<Self, MinimizeError> {
+        let mut column_variables = single_bits(table, output)?;
+        if column_variables.len() > KARNAUGH_VARIABLES {
+            return Err(MinimizeError::TooManyVariables(column_variables.len()));
+        }
+
+        let row_variables: Vec<_> = column_variables.drain(..column_variables.len() / 2).collect();
+        let (row_values, column_values) = (gray_code(row_variables.len()), gray_code(column_variables.len()));
+        let cells = row_values
+            .iter()
+            .map(|&row| {
+                column_values
+                    .iter()
+                    .map(|&column| {
+                        let idx = (row << column_variables.len() | column) as usize;
+                        table.rows[idx].outputs[output].bit(0)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self { row_variables, column_variables, row_values, column_values, cells })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Signal;
+    use crate::truth_table::Row;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// Table of single-bit inputs and one output, given its value on each row
+    fn table(inputs: &[&str], outputs: &[Signal]) -> TruthTable {
+        let column = |name: &str| Column { name: name.to_string(), width: 1 };
+        let rows = outputs
+            .iter()
+            .enumerate()
+            .map(|(row, &output)| Row {
+                inputs: (0..inputs.len()).rev().map(|bit| (row as u64) >> bit & 1).collect(),
+                outputs: vec![output],
+            })
+            .collect();
+        TruthTable { inputs: inputs.iter().map(|&name| column(name)).collect(), outputs: vec![column("Y")], rows }
+    }
+
+    #[test]
+    fn finds_minimal_sums() {
+        let abcd = names(&["A", "B", "C", "D"]);
+        // Row 8 is covered as well by A & !B, the other minimal sum
+        let expr = minimal_sum(&abcd, &[4, 8, 10, 11, 12, 15], &[9, 14]);
+        assert_eq!(expr.to_string(), "A & C | A & !D | B & !C & !D");
+
+        assert_eq!(minimal_sum(&abcd, &[], &[1, 2]).to_string(), "0");
+        assert_eq!(minimal_sum(&abcd, &(0..16).collect::<Vec<_>>(), &[]).to_string(), "1");
+        assert_eq!(minimal_sum(&names(&["A", "B"]), &[1, 2], &[]).to_string(), "!A & B | A & !B");
+    }
+
+    #[test]
+    fn sums_match_the_table() {
+        let abc = names(&["A", "B", "C"]);
+        for function in 0..256u64 {
+            let minterms: Vec<_> = (0..8).filter(|row| function >> row & 1 == 1).collect();
+            let expr = minimal_sum(&abc, &minterms, &[]);
+            for row in 0..8 {
+                let value = |name: &str| row >> (2 - abc.iter().position(|var| var == name).unwrap()) & 1 == 1;
+                assert_eq!(expr.evaluate(&value), minterms.contains(&row), "{function:08b} on row {row}");
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_outputs_are_free() {
+        let (low, high, x) = (Signal::known(0), Signal::known(1), Signal::unknown(1));
+        let table = table(&["A", "B"], &[low, high, high, x]);
+        assert_eq!(table.minimize(0).unwrap().to_string(), "A | B");
+
+        let mut bus = table.clone();
+        bus.inputs[1].width = 2;
+        assert_eq!(bus.minimize(0), Err(MinimizeError::Bus("B".to_string())));
+    }
+
+    #[test]
+    fn maps_in_gray_code_order() {
+        let outputs: Vec<_> = (0..8).map(|row| Signal::known((row == 2 || row == 7) as u64)).collect();
+        let map = KarnaughMap::new(&table(&["A", "B", "C"], &outputs), 0).unwrap();
+        assert_eq!(map.row_variables, ["A"]);
+        assert_eq!(map.column_values, [0, 1, 3, 2]);
+        let cells: Vec<String> = map.cells.iter().map(|row| row.iter().map(Logic::as_char).collect()).collect();
+        assert_eq!(cells, ["0001", "0010"]);
+
+        let wide = table(&["A", "B", "C", "D", "E", "F", "G"], &vec![Signal::known(0); 128]);
+        assert_eq!(KarnaughMap::new(&wide, 0), Err(MinimizeError::TooManyVariables(7)));
+    }
+}
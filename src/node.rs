@@ -1,6 +1,14 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::{prelude::*, shapes::Circle, entity::ShapeBundle};
-use crate::{cursor::Cursor, constants::{Depth, Colors, RADIUS}};
+use crate::{
+    circuit::{mask, Signal},
+    cursor::Cursor,
+    constants::{Depth, Colors, RADIUS},
+    diagnostics::Diagnostics,
+    file::prompt_closed,
+    history::{Edit, History},
+    ui::NodeLabel,
+};
 
 pub struct NodePlugin;
 
@@ -10,13 +18,15 @@ impl Plugin for NodePlugin {
             .insert_resource(HoveredNode( None ))
             .add_system(hover_node)
             .add_system(set_node_color)
-            .add_system(toggle_node);
+            .add_system(toggle_node)
+            .add_system(type_node_value.with_run_criteria(prompt_closed));
     }
 }
 
 #[derive(Bundle)]
 pub struct NodeSpawner {
-    node: Node,
+    pub node: Node,
+    direction: PinDirection,
     shape: ShapeBundle
 }
 
@@ -27,7 +37,8 @@ impl NodeSpawner {
 
     pub fn from_pos(pos: Vec2) -> Self {
         Self {
-            node: Node(false), 
+            node: Node::new(1),
+            direction: PinDirection::Sink,
             shape: GeometryBuilder::build_as(
                 &Circle { center: Vec2::ZERO, radius: RADIUS },
                 DrawMode::Fill(FillMode::color(Color::BLACK)), // will be set to NodeColors.off automatically
@@ -35,10 +46,43 @@ impl NodeSpawner {
             )
         }
     }
+
+    pub fn width(mut self, width: u8) -> Self {
+        self.node.width = width;
+        self
+    }
+
+    pub fn direction(mut self, direction: PinDirection) -> Self {
+        self.direction = direction;
+        self
+    }
 }
 
+/// Which end of edges a node goes at. Edges always run from a driver to a sink.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinDirection {
+    /// Inputs of the circuit and outputs of gates
+    Driver,
+    /// Outputs of the circuit and inputs of gates
+    Sink,
+}
+
+/// A pin carrying `width` bits, stored in the low bits of `value`. Nothing drives it at first.
 #[derive(Component, Clone)]
-pub struct Node(pub bool);
+pub struct Node {
+    pub value: Signal,
+    pub width: u8,
+}
+
+impl Node {
+    pub fn new(width: u8) -> Self {
+        Self { value: Signal::floating(width), width }
+    }
+
+    pub fn is_bus(&self) -> bool {
+        self.width > 1
+    }
+}
 
 /// This holds a reference to the node that is currently hovered over by the mouse
 #[derive(Resource)]
@@ -54,23 +98,84 @@ fn hover_node(query: Query<(Entity, &GlobalTransform), With<Node>>, mut hovered:
     hovered.0 = None;
 }
 
-fn set_node_color(mut query: Query<(Entity, &Node, &mut DrawMode)>, hovered: Res<HoveredNode>) {
+fn set_node_color(
+    mut query: Query<(Entity, &Node, &mut DrawMode)>,
+    hovered: Res<HoveredNode>,
+    diagnostics: Res<Diagnostics>,
+) {
     for (entity, node, mut draw_mode) in &mut query {
         let DrawMode::Fill(ref mut fill_mode) = *draw_mode else { return };
         
-        if Some(entity) == hovered.0 {
-            fill_mode.color = Colors::highlighted(node.0);
+        if diagnostics.conflicts.contains(&entity) {
+            fill_mode.color = Colors::CONFLICT;
+        }
+        else if Some(entity) == hovered.0 {
+            fill_mode.color = Colors::highlighted_signal(node.value);
         }
         else {
-            fill_mode.color = Colors::value(node.0);
+            fill_mode.color = Colors::signal(node.value);
         }
     }
 }
 
-fn toggle_node(mut query: Query<&mut Node>, hovered: Res<HoveredNode>, mouse_input: Res<Input<MouseButton>>) {
+/// Flips the node under the mouse when it is clicked, unless it is named after a constant
+fn toggle_node(
+    mut query: Query<(&mut Node, Option<&NodeLabel>)>,
+    hovered: Res<HoveredNode>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut history: ResMut<History>,
+) {
     if mouse_input.just_pressed(MouseButton::Left) {
         let Some(hovered) = hovered.0 else { return };
-        let Ok(mut node) = query.get_mut(hovered) else { return };
-        node.0 = !node.0;
+        let Ok((mut node, label)) = query.get_mut(hovered) else { return };
+        if label.and_then(NodeLabel::constant).is_some() {
+            return;
+        }
+
+        // Buses count up instead, their value is easier to type in. Undefined bits read as low.
+        let (from, bits) = (node.value, node.value.bits);
+        node.value = Signal::known(if node.is_bus() { mask(bits.wrapping_add(1), node.width) } else { bits ^ 1 });
+        history.push(Edit::SetNode { node: hovered, from, to: node.value });
+    }
+}
+
+/// Types the value of the bus under the mouse in hexadecimal, from the right. Backspace removes the last digit.
+fn type_node_value(
+    mut query: Query<&mut Node>,
+    hovered: Res<HoveredNode>,
+    keys: Res<Input<KeyCode>>,
+    mut history: ResMut<History>,
+) {
+    use KeyCode::*;
+    const DIGITS: [KeyCode; 16] = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, A, B, C, D, E, F];
+
+    // Those keys are shortcuts
+    if keys.any_pressed([LControl, RControl]) {
+        return;
+    }
+    let Some(hovered) = hovered.0 else { return };
+    let Ok(mut node) = query.get_mut(hovered) else { return };
+    if !node.is_bus() {
+        return;
+    }
+
+    // Undefined bits read as low, and typing drives them all
+    let (from, mut bits) = (node.value, node.value.bits);
+    let mut typed = false;
+    for key in keys.get_just_pressed() {
+        if let Some(digit) = DIGITS.iter().position(|digit| digit == key) {
+            bits = mask(bits << 4 | digit as u64, node.width);
+            typed = true;
+        } else if *key == Back {
+            bits >>= 4;
+            typed = true;
+        }
+    }
+    if typed && Signal::known(bits) != from {
+        node.value = Signal::known(bits);
+    }
+
+    if node.value != from {
+        history.push(Edit::SetNode { node: hovered, from, to: node.value });
     }
 }
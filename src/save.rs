@@ -0,0 +1,994 @@
+//! Versioned file format for circuits.
+//!
+//! Edges refer to pins by their position in the file (the n-th input node, the n-th gate...),
+//! since entity ids don't survive between runs.
+//!
+//! History of the format:
+//! - 1: inputs, gates and edges
+//! - 2: output nodes
+//! - 3: number of inputs of each gate
+//! - 4: flip-flops, with their state and second output
+//! - 5: clocks
+//! - 6: buses, with the width of nodes and gates, and input values saved as integers
+//! - 7: custom components
+//! - 8: library files, from which circuit files only keep the pins of the components they use
+//! - 9: propagation delays of gates and edges
+//! - 10: resolution of pins driven by several edges
+//! - 11: edges always run from a driver to a sink
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::circuit::{Circuit, ClockConfig, GateState, GateType, Interface, PinId, Resolution, MAX_WIDTH};
+
+/// Version written in new files. Bump it whenever the format changes in a way older files can't be read as-is,
+/// and convert them in [`CircuitFile::from_ron`].
+pub const VERSION: u32 = 11;
+
+/// First version with library files
+const LIBRARY_VERSION: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitFile {
+    pub version: u32,
+    pub inputs: Vec<InputSave>,
+    /// Missing before version 2
+    #[serde(default)]
+    pub outputs: Vec<OutputSave>,
+    pub gates: Vec<GateSave>,
+    pub edges: Vec<EdgeSave>,
+    /// Definitions of the custom components, which can only use the ones defined before them.
+    /// Always empty in the definitions themselves, as they share the list of the file.
+    /// Missing before version 7.
+    #[serde(default)]
+    pub components: Vec<ComponentSave>,
+    /// Missing before version 9
+    #[serde(default)]
+    pub delays: Delays,
+    /// Missing before version 10
+    #[serde(default)]
+    pub resolution: Resolution,
+}
+
+/// Propagation delays used by the editor, in ticks. Gates and edges can also have their own.
+/// [`CircuitFile::to_circuit`] ignores them, as wires always take one step there and gates none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delays {
+    /// Delay of edges without a delay of their own
+    pub edge: u32,
+    /// Delay of gates whose type has no delay
+    pub gate: u32,
+    /// Delays of gate types, taking precedence over `gate`
+    pub kinds: Vec<(GateType, u32)>,
+}
+
+impl Default for Delays {
+    fn default() -> Self {
+        Self { edge: 1, gate: 0, kinds: vec![] }
+    }
+}
+
+impl Delays {
+    /// Gate types that share a delay, so that all clocks have the same whatever their period
+    fn key(kind: GateType) -> GateType {
+        match kind {
+            GateType::Clock(_) => GateType::Clock(ClockConfig::default()),
+            kind => kind,
+        }
+    }
+
+    /// Delay of gates of the given type, if it has one
+    pub fn kind(&self, kind: GateType) -> Option<u32> {
+        let key = Self::key(kind);
+        self.kinds.iter().find(|(kind, _)| *kind == key).map(|&(_, delay)| delay)
+    }
+
+    pub fn set_kind(&mut self, kind: GateType, delay: u32) {
+        let key = Self::key(kind);
+        match self.kinds.iter_mut().find(|(kind, _)| *kind == key) {
+            Some((_, old)) => *old = delay,
+            None => self.kinds.push((key, delay)),
+        }
+    }
+
+    /// Delay of a gate, from its own delay, then the one of its type, then the default one
+    pub fn gate(&self, kind: GateType, own: Option<u32>) -> u32 {
+        own.or_else(|| self.kind(kind)).unwrap_or(self.gate)
+    }
+
+    /// Delay of an edge, from its own delay or the default one
+    pub fn edge(&self, own: Option<u32>) -> u32 {
+        own.unwrap_or(self.edge)
+    }
+}
+
+/// A circuit packaged as a component, whose pins are the nodes of its side panels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentSave {
+    pub name: String,
+    /// Library file the component comes from, in which case circuit files only keep its pins
+    /// and get its definition from the library in [`CircuitFile::link_libraries`].
+    /// Missing before version 8.
+    #[serde(default)]
+    pub library: Option<PathBuf>,
+    pub circuit: CircuitFile,
+}
+
+impl ComponentSave {
+    /// Widths of the inputs and outputs of the component
+    pub fn pins(&self) -> (Vec<u8>, Vec<u8>) {
+        (
+            self.circuit.inputs.iter().map(|input| input.width).collect(),
+            self.circuit.outputs.iter().map(|output| output.width).collect(),
+        )
+    }
+
+    /// What circuit files keep of the component: everything if it was made in the circuit, and only its pins if it comes from a library
+    pub fn stub(&self) -> ComponentSave {
+        let mut component = self.clone();
+        if component.library.is_some() {
+            component.circuit.gates.clear();
+            component.circuit.edges.clear();
+        }
+        component
+    }
+
+    /// Whether both components have pins with the same labels and widths
+    fn same_pins(&self, other: &ComponentSave) -> bool {
+        let inputs = |c: &ComponentSave| c.circuit.inputs.iter().map(|i| (i.label.clone(), i.width)).collect::<Vec<_>>();
+        let outputs = |c: &ComponentSave| c.circuit.outputs.iter().map(|o| (o.label.clone(), o.width)).collect::<Vec<_>>();
+        inputs(self) == inputs(other) && outputs(self) == outputs(other)
+    }
+}
+
+/// Components kept in their own file, to be shared by several circuits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryFile {
+    pub version: u32,
+    /// Components of the library, which can only use the ones defined before them
+    pub components: Vec<ComponentSave>,
+}
+
+/// A node of the input panel, in order from top to bottom
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputSave {
+    pub label: String,
+    #[serde(deserialize_with = "bits_or_bool")]
+    pub value: u64,
+    /// Missing before version 6
+    #[serde(default)]
+    pub width: u8,
+}
+
+/// A node of the output panel, in order from top to bottom
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputSave {
+    pub label: String,
+    /// Missing before version 6
+    #[serde(default)]
+    pub width: u8,
+}
+
+/// Values of inputs were booleans before version 6
+fn bits_or_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Bool(bool),
+        Bits(u64),
+    }
+
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(value) => value as u64,
+        Value::Bits(value) => value,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GateSave {
+    pub kind: GateType,
+    /// Missing before version 6
+    #[serde(default)]
+    pub width: u8,
+    /// Missing before version 3
+    #[serde(default)]
+    pub inputs: usize,
+    /// Missing before version 4
+    #[serde(default)]
+    pub state: GateState,
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    /// Delay in ticks, if it isn't the one of its type. Missing before version 9.
+    #[serde(default)]
+    pub delay: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeSave {
+    pub from: PinRef,
+    pub to: PinRef,
+    /// Delay in ticks, if it isn't the default one. Missing before version 9.
+    #[serde(default)]
+    pub delay: Option<u32>,
+}
+
+/// Stable reference to a pin, using indices into the lists of the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PinRef {
+    Input(usize),
+    Output(usize),
+    GateInput { gate: usize, pin: usize },
+    GateOutput {
+        gate: usize,
+        /// Missing before version 4, when gates only had one output
+        #[serde(default)]
+        pin: usize,
+    },
+}
+
+impl PinRef {
+    /// Whether the pin drives the edges attached to it: inputs of the circuit and outputs of gates
+    pub fn is_driver(&self) -> bool {
+        matches!(self, PinRef::Input(_) | PinRef::GateOutput { .. })
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    /// The file was written by a newer version of the program
+    UnsupportedVersion(u32),
+    /// An edge refers to a pin that isn't in the file
+    InvalidPin(PinRef),
+    /// A gate has a number of inputs its type doesn't allow
+    InvalidInputs { gate: usize, inputs: usize },
+    /// A clock has a period of zero, or stays high for longer than its period
+    InvalidClock { gate: usize },
+    /// A node or gate has a number of bits it can't carry
+    InvalidWidth { width: u8 },
+    /// An edge connects pins of different widths
+    WidthMismatch { from: PinRef, to: PinRef },
+    /// An edge doesn't run from a driver to a sink
+    WrongDirection { from: PinRef, to: PinRef },
+    /// A gate uses a component that isn't defined, or isn't defined before the component it is part of
+    InvalidComponent { gate: usize },
+    /// A component isn't in the library it comes from anymore
+    MissingComponent { name: String, library: PathBuf },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{e}"),
+            LoadError::Parse(e) => write!(f, "invalid circuit file: {e}"),
+            LoadError::UnsupportedVersion(v) => write!(f, "unsupported file version {v} (latest is {VERSION})"),
+            LoadError::InvalidPin(pin) => write!(f, "edge refers to a missing pin: {pin:?}"),
+            LoadError::InvalidInputs { gate, inputs } => write!(f, "gate {gate} can't have {inputs} inputs"),
+            LoadError::InvalidClock { gate } => write!(f, "clock {gate} has an invalid period"),
+            LoadError::InvalidWidth { width } => write!(f, "invalid width of {width} bits"),
+            LoadError::WidthMismatch { from, to } => write!(f, "edge connects pins of different widths: {from:?} and {to:?}"),
+            LoadError::WrongDirection { from, to } => write!(f, "edge doesn't run from a driver to a sink: {from:?} to {to:?}"),
+            LoadError::InvalidComponent { gate } => write!(f, "gate {gate} uses a missing component"),
+            LoadError::MissingComponent { name, library } => {
+                write!(f, "component {name} is missing from library {}", library.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for LoadError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        LoadError::Parse(e)
+    }
+}
+
+impl Default for CircuitFile {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            inputs: vec![],
+            outputs: vec![],
+            gates: vec![],
+            edges: vec![],
+            components: vec![],
+            delays: Delays::default(),
+            resolution: Resolution::default(),
+        }
+    }
+}
+
+impl CircuitFile {
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("circuit files only contain serializable data")
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, LoadError> {
+        /// Only reads the version, so we know how to parse the rest
+        #[derive(Deserialize)]
+        #[serde(rename = "CircuitFile")]
+        struct Header {
+            version: u32,
+        }
+
+        let Header { version } = ron::from_str(s)?;
+
+        if version > VERSION || version == 0 {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        // Fields added since then are filled with their default value, and upgraded below
+        let mut file: CircuitFile = ron::from_str(s)?;
+        file.upgrade(version);
+        for component in &mut file.components {
+            component.circuit.upgrade(version);
+        }
+
+        validate_components(&file.components)?;
+        file.validate(&file.components)?;
+        Ok(file)
+    }
+
+    /// Fills the fields added since `version` with the values older versions implied
+    fn upgrade(&mut self, version: u32) {
+        // Version 2 added the output panel, which is empty by default
+
+        if version < 3 {
+            for gate in &mut self.gates {
+                gate.inputs = gate.kind.default_inputs(1);
+            }
+        }
+
+        if version < 6 {
+            self.inputs.iter_mut().for_each(|input| input.width = 1);
+            self.outputs.iter_mut().for_each(|output| output.width = 1);
+            self.gates.iter_mut().for_each(|gate| gate.width = 1);
+        }
+
+        // Edges could be drawn both ways, and between two drivers or two sinks
+        if version < 11 {
+            self.edges.retain_mut(|edge| {
+                if !edge.from.is_driver() {
+                    std::mem::swap(&mut edge.from, &mut edge.to);
+                }
+                edge.from.is_driver() && !edge.to.is_driver()
+            });
+        }
+
+        self.version = VERSION;
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_ron())
+    }
+
+    /// Reads a circuit file. Components from libraries only have their pins until [`CircuitFile::link_libraries`] is called.
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    /// Replaces the components that come from libraries with their current definition,
+    /// and returns the names of those whose pins changed since the file was saved.
+    /// Gates using those get their new number of inputs, and lose the edges of pins that are gone or changed width.
+    pub fn link_libraries(&mut self) -> Result<Vec<String>, LoadError> {
+        let mut libraries = HashMap::new();
+        let mut changed = vec![];
+
+        for idx in 0..self.components.len() {
+            let Some(path) = self.components[idx].library.clone() else { continue };
+            if !libraries.contains_key(&path) {
+                libraries.insert(path.clone(), LibraryFile::load(&path)?);
+            }
+            let library = &libraries[&path];
+
+            let missing = |name: &str| LoadError::MissingComponent { name: name.to_string(), library: path.clone() };
+            let name = &self.components[idx].name;
+            let definition = library.components.iter().find(|c| &c.name == name).ok_or_else(|| missing(name))?;
+
+            // Components of the library refer to each other by their index in the library, and need the same ones here
+            let (before, _) = self.components.split_at(idx);
+            let circuit = relink(&definition.circuit, |j| {
+                let dependency = &library.components[j].name;
+                before
+                    .iter()
+                    .position(|c| &c.name == dependency && c.library.as_ref() == Some(&path))
+                    .ok_or_else(|| missing(dependency))
+            })?;
+
+            let definition = ComponentSave { library: Some(path.clone()), circuit, ..definition.clone() };
+            if !definition.same_pins(&self.components[idx]) {
+                changed.push(idx);
+            }
+            self.components[idx] = definition;
+        }
+
+        if !changed.is_empty() {
+            let library = self.components.clone();
+            for component in &mut self.components {
+                component.circuit.refit(&library, &changed);
+            }
+            self.refit(&library, &changed);
+        }
+
+        validate_components(&self.components)?;
+        self.validate(&self.components)?;
+        Ok(changed.into_iter().map(|idx| self.components[idx].name.clone()).collect())
+    }
+
+    /// Fits the gates using the given components of `library` to their current pins, and drops the edges that don't fit anymore
+    fn refit(&mut self, library: &[ComponentSave], changed: &[usize]) {
+        for gate in &mut self.gates {
+            let GateType::Custom(idx) = gate.kind else { continue };
+            if changed.contains(&idx) {
+                gate.inputs = library[idx].circuit.inputs.len();
+            }
+        }
+
+        let edges = std::mem::take(&mut self.edges);
+        self.edges = edges
+            .into_iter()
+            .filter(|edge| {
+                let from = self.pin_width(edge.from, library);
+                from.is_some() && from == self.pin_width(edge.to, library)
+            })
+            .collect();
+    }
+
+    /// Builds the circuit for headless simulation, along with the pins of its side panels
+    pub fn to_circuit(&self) -> (Circuit, Interface) {
+        let mut circuit = Circuit::new();
+        circuit.set_resolution(self.resolution);
+        let interface = self.build(&mut circuit, &self.components);
+        (circuit, interface)
+    }
+
+    /// Adds the circuit to `circuit`, inlining the custom components from `library`, and returns the pins of its side panels
+    fn build(&self, circuit: &mut Circuit, library: &[ComponentSave]) -> Interface {
+        let mut interface = Interface::default();
+
+        for input in &self.inputs {
+            let pin = circuit.add_bus(input.width);
+            circuit.set_value(pin, input.value);
+            interface.inputs.push((input.label.clone(), pin));
+        }
+        for output in &self.outputs {
+            interface.outputs.push((output.label.clone(), circuit.add_bus(output.width)));
+        }
+
+        let gates: Vec<_> = self
+            .gates
+            .iter()
+            .map(|gate| match gate.kind {
+                // The panels of the component become the pins of the gate
+                GateType::Custom(idx) => {
+                    let interface = library[idx].circuit.build(circuit, library);
+                    let pins = |pins: Vec<(String, PinId)>| pins.into_iter().map(|(_, pin)| pin).collect();
+                    (pins(interface.inputs), pins(interface.outputs))
+                }
+                kind => {
+                    let id = circuit.add_gate_with(kind, gate.inputs, gate.width);
+                    circuit.set_state(id, gate.state);
+                    let gate = circuit.gate(id).unwrap();
+                    (gate.inputs.clone(), gate.outputs.clone())
+                }
+            })
+            .collect();
+
+        let pin = |pin: PinRef| -> PinId {
+            match pin {
+                PinRef::Input(idx) => interface.inputs[idx].1,
+                PinRef::Output(idx) => interface.outputs[idx].1,
+                PinRef::GateInput { gate, pin } => gates[gate].0[pin],
+                PinRef::GateOutput { gate, pin } => gates[gate].1[pin],
+            }
+        };
+
+        for edge in &self.edges {
+            let (from, to) = (pin(edge.from), pin(edge.to));
+            circuit.connect(from, to).expect("widths were validated when loading");
+        }
+
+        interface
+    }
+
+    /// Widths of the inputs and outputs of a gate, if it is valid
+    fn gate_pins(gate: &GateSave, library: &[ComponentSave]) -> Option<(Vec<u8>, Vec<u8>)> {
+        match gate.kind {
+            GateType::Custom(idx) => Some(library.get(idx)?.pins()),
+            kind => Some((kind.input_widths(gate.inputs, gate.width), kind.output_widths(gate.width))),
+        }
+    }
+
+    /// Number of bits carried by a pin, if it exists
+    fn pin_width(&self, pin: PinRef, library: &[ComponentSave]) -> Option<u8> {
+        match pin {
+            PinRef::Input(idx) => Some(self.inputs.get(idx)?.width),
+            PinRef::Output(idx) => Some(self.outputs.get(idx)?.width),
+            PinRef::GateInput { gate, pin } => Self::gate_pins(self.gates.get(gate)?, library)?.0.get(pin).copied(),
+            PinRef::GateOutput { gate, pin } => Self::gate_pins(self.gates.get(gate)?, library)?.1.get(pin).copied(),
+        }
+    }
+
+    /// Checks that every gate has a valid width and number of inputs, clocks a valid period and components a definition
+    /// in `library`, and every edge connects two pins of the same width that exist
+    fn validate(&self, library: &[ComponentSave]) -> Result<(), LoadError> {
+        let widths = self.inputs.iter().map(|input| input.width).chain(self.outputs.iter().map(|output| output.width));
+        for width in widths {
+            if !(1..=MAX_WIDTH).contains(&width) {
+                return Err(LoadError::InvalidWidth { width });
+            }
+        }
+
+        for (idx, gate) in self.gates.iter().enumerate() {
+            if let GateType::Custom(component) = gate.kind {
+                let Some(component) = library.get(component) else {
+                    return Err(LoadError::InvalidComponent { gate: idx });
+                };
+                if gate.inputs != component.circuit.inputs.len() {
+                    return Err(LoadError::InvalidInputs { gate: idx, inputs: gate.inputs });
+                }
+                continue;
+            }
+
+            if !gate.kind.width_range().contains(&gate.width) {
+                return Err(LoadError::InvalidWidth { width: gate.width });
+            }
+            if !gate.kind.input_range(gate.width).contains(&gate.inputs) {
+                return Err(LoadError::InvalidInputs { gate: idx, inputs: gate.inputs });
+            }
+            if let GateType::Clock(config) = gate.kind {
+                if !config.is_valid() {
+                    return Err(LoadError::InvalidClock { gate: idx });
+                }
+            }
+        }
+
+        for &EdgeSave { from, to, .. } in &self.edges {
+            let from_width = self.pin_width(from, library).ok_or(LoadError::InvalidPin(from))?;
+            let to_width = self.pin_width(to, library).ok_or(LoadError::InvalidPin(to))?;
+
+            if from_width != to_width {
+                return Err(LoadError::WidthMismatch { from, to });
+            }
+            if !from.is_driver() || to.is_driver() {
+                return Err(LoadError::WrongDirection { from, to });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that every component is valid and only uses the ones defined before it, which rules out recursion
+fn validate_components(components: &[ComponentSave]) -> Result<(), LoadError> {
+    for (idx, component) in components.iter().enumerate() {
+        if !component.circuit.components.is_empty() {
+            return Err(LoadError::InvalidComponent { gate: idx });
+        }
+        component.circuit.validate(&components[..idx])?;
+    }
+    Ok(())
+}
+
+/// Copy of the circuit whose custom gates use the component at `map(idx)` instead of `idx`
+fn relink<E>(circuit: &CircuitFile, map: impl Fn(usize) -> Result<usize, E>) -> Result<CircuitFile, E> {
+    let mut circuit = circuit.clone();
+    for gate in &mut circuit.gates {
+        if let GateType::Custom(idx) = gate.kind {
+            gate.kind = GateType::Custom(map(idx)?);
+        }
+    }
+    Ok(circuit)
+}
+
+impl Default for LibraryFile {
+    fn default() -> Self {
+        Self { version: VERSION, components: vec![] }
+    }
+}
+
+impl LibraryFile {
+    /// Gathers the components made in the circuit or already from the library at `path`,
+    /// which must not use components from other libraries. Returns the name of the first one that does otherwise.
+    pub fn from_components(components: &[ComponentSave], path: &Path) -> Result<Self, String> {
+        let local: Vec<_> = (0..components.len())
+            .filter(|&idx| components[idx].library.as_deref().is_none_or(|library| library == path))
+            .collect();
+
+        let components = local
+            .iter()
+            .map(|&idx| {
+                let component = &components[idx];
+                let circuit = relink(&component.circuit, |dependency| {
+                    local.iter().position(|&local| local == dependency).ok_or(())
+                })
+                .map_err(|()| component.name.clone())?;
+                Ok(ComponentSave { library: None, circuit, ..component.clone() })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { version: VERSION, components })
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("library files only contain serializable data")
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, LoadError> {
+        /// Only reads the version, so we know how to parse the rest
+        #[derive(Deserialize)]
+        #[serde(rename = "LibraryFile")]
+        struct Header {
+            version: u32,
+        }
+
+        let Header { version } = ron::from_str(s)?;
+
+        if !(LIBRARY_VERSION..=VERSION).contains(&version) {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        let mut file: LibraryFile = ron::from_str(s)?;
+        for component in &mut file.components {
+            component.circuit.upgrade(version);
+        }
+        file.version = VERSION;
+
+        validate_components(&file.components)?;
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_ron())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    /// Adds the components of the library at `path` to `components`, and returns the names of those that were left out
+    /// because the name is taken by a component from somewhere else, or they use one that was left out.
+    /// Components that were already imported from this library are kept as they are.
+    pub fn import(&self, path: &Path, components: &mut Vec<ComponentSave>) -> Vec<String> {
+        // Index in `components` of each component of the library
+        let mut indices: Vec<Option<usize>> = vec![];
+        let mut skipped = vec![];
+
+        for component in &self.components {
+            let idx = match components.iter().position(|c| c.name == component.name) {
+                Some(idx) if components[idx].library.as_deref() == Some(path) => Some(idx),
+                Some(_) => None,
+                None => relink(&component.circuit, |idx| indices[idx].ok_or(())).ok().map(|circuit| {
+                    components.push(ComponentSave { library: Some(path.to_owned()), circuit, ..component.clone() });
+                    components.len() - 1
+                }),
+            };
+
+            if idx.is_none() {
+                skipped.push(component.name.clone());
+            }
+            indices.push(idx);
+        }
+
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::ClockConfig;
+
+    fn not_gate() -> CircuitFile {
+        CircuitFile {
+            inputs: vec![InputSave { label: "A".into(), value: 1, width: 1 }],
+            gates: vec![GateSave {
+                kind: GateType::Not,
+                width: 1,
+                inputs: 1,
+                state: GateState::default(),
+                pos: [100.0, 20.0],
+                size: [120.0, 120.0],
+                delay: None,
+            }],
+            edges: vec![EdgeSave { from: PinRef::Input(0), to: PinRef::GateInput { gate: 0, pin: 0 }, delay: None }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = not_gate();
+        assert_eq!(CircuitFile::from_ron(&file.to_ron()).unwrap(), file);
+    }
+
+    #[test]
+    fn simulates_saved_circuit() {
+        let mut file = not_gate();
+        file.outputs.push(OutputSave { label: "Y0".into(), width: 1 });
+        file.edges.push(EdgeSave { from: PinRef::GateOutput { gate: 0, pin: 0 }, to: PinRef::Output(0), delay: None });
+
+        let (mut circuit, interface) = file.to_circuit();
+        circuit.run_until_stable(10).unwrap();
+        assert!(!circuit.get(interface.outputs[0].1));
+
+        circuit.set(interface.inputs[0].1, false);
+        circuit.run_until_stable(10).unwrap();
+        assert!(circuit.get(interface.outputs[0].1));
+    }
+
+    #[test]
+    fn reads_version_1() {
+        let file = r#"(
+            version: 1,
+            inputs: [(label: "A", value: false)],
+            gates: [(kind: And, pos: (0.0, 0.0), size: (120.0, 120.0))],
+            edges: [(from: Input(0), to: GateInput(gate: 0, pin: 1))],
+        )"#;
+
+        let file = CircuitFile::from_ron(file).unwrap();
+        assert_eq!(file.version, VERSION);
+        assert_eq!(file.inputs.len(), 1);
+        assert!(file.outputs.is_empty());
+        assert_eq!(file.inputs[0], InputSave { label: "A".into(), value: 0, width: 1 });
+        assert_eq!(file.gates[0].inputs, 2);
+        assert_eq!(file.gates[0].width, 1);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let file = CircuitFile { version: VERSION + 1, ..not_gate() };
+        assert!(matches!(CircuitFile::from_ron(&file.to_ron()), Err(LoadError::UnsupportedVersion(_))));
+    }
+
+    #[test]
+    fn rejects_dangling_edges() {
+        let mut file = not_gate();
+        file.edges.push(EdgeSave { from: PinRef::GateOutput { gate: 3, pin: 0 }, to: PinRef::Input(0), delay: None });
+        assert!(matches!(CircuitFile::from_ron(&file.to_ron()), Err(LoadError::InvalidPin(_))));
+    }
+
+    #[test]
+    fn edges_run_from_drivers_to_sinks() {
+        let mut file = not_gate();
+        file.edges.push(EdgeSave { from: PinRef::GateInput { gate: 0, pin: 0 }, to: PinRef::Input(0), delay: None });
+        assert!(matches!(CircuitFile::from_ron(&file.to_ron()), Err(LoadError::WrongDirection { .. })));
+
+        // Older files get their backward edges turned around, and lose those between two drivers
+        file.version = 10;
+        file.edges.push(EdgeSave { from: PinRef::GateOutput { gate: 0, pin: 0 }, to: PinRef::Input(0), delay: None });
+        let loaded = CircuitFile::from_ron(&file.to_ron()).unwrap();
+        let edges: Vec<_> = loaded.edges.iter().map(|edge| (edge.from, edge.to)).collect();
+        assert_eq!(edges, [(PinRef::Input(0), PinRef::GateInput { gate: 0, pin: 0 }); 2]);
+    }
+
+    #[test]
+    fn keeps_flip_flop_state() {
+        let mut file = not_gate();
+        file.outputs.push(OutputSave { label: "Y0".into(), width: 1 });
+        file.gates.push(GateSave {
+            kind: GateType::DFlipFlop,
+            width: 1,
+            inputs: 2,
+            state: GateState { q: true, ..Default::default() },
+            pos: [300.0, 20.0],
+            size: [200.0, 120.0],
+            delay: None,
+        });
+        file.edges.push(EdgeSave { from: PinRef::GateOutput { gate: 1, pin: 1 }, to: PinRef::Output(0), delay: None });
+
+        let file = CircuitFile::from_ron(&file.to_ron()).unwrap();
+        let (mut circuit, interface) = file.to_circuit();
+        circuit.run_until_stable(10).unwrap();
+        assert!(!circuit.get(interface.outputs[0].1));
+
+        let mut dangling = file.clone();
+        dangling.edges.push(EdgeSave { from: PinRef::GateOutput { gate: 0, pin: 1 }, to: PinRef::Output(0), delay: None });
+        assert!(matches!(CircuitFile::from_ron(&dangling.to_ron()), Err(LoadError::InvalidPin(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_clocks() {
+        let mut file = not_gate();
+        file.gates[0].kind = GateType::Clock(ClockConfig { period: 0, high: 0 });
+        file.gates[0].inputs = 0;
+        file.edges.clear();
+        assert!(matches!(CircuitFile::from_ron(&file.to_ron()), Err(LoadError::InvalidClock { gate: 0 })));
+    }
+
+    #[test]
+    fn rejects_edges_between_different_widths() {
+        let mut file = not_gate();
+        file.inputs[0].width = 8;
+        assert!(matches!(CircuitFile::from_ron(&file.to_ron()), Err(LoadError::WidthMismatch { .. })));
+
+        file.gates[0].width = 8;
+        let (mut circuit, interface) = CircuitFile::from_ron(&file.to_ron()).unwrap().to_circuit();
+        let gate = circuit.gates().next().unwrap().1.clone();
+        circuit.run_until_stable(10).unwrap();
+        assert_eq!(circuit.value(interface.inputs[0].1), 1);
+        assert_eq!(circuit.value(gate.outputs[0]), 0xfe);
+    }
+
+    /// Half adder, as a component with inputs A and B, and outputs S and C
+    fn half_adder() -> ComponentSave {
+        let gate = |kind| GateSave {
+            kind,
+            width: 1,
+            inputs: 2,
+            state: GateState::default(),
+            pos: [0.0, 0.0],
+            size: [120.0, 120.0],
+            delay: None,
+        };
+        let edge = |from, to| EdgeSave { from, to, delay: None };
+        let input = |label: &str| InputSave { label: label.into(), value: 0, width: 1 };
+        let output = |label: &str| OutputSave { label: label.into(), width: 1 };
+
+        ComponentSave {
+            name: "Half adder".into(),
+            library: None,
+            circuit: CircuitFile {
+                inputs: vec![input("A"), input("B")],
+                outputs: vec![output("S"), output("C")],
+                gates: vec![gate(GateType::Xor), gate(GateType::And)],
+                edges: vec![
+                    edge(PinRef::Input(0), PinRef::GateInput { gate: 0, pin: 0 }),
+                    edge(PinRef::Input(1), PinRef::GateInput { gate: 0, pin: 1 }),
+                    edge(PinRef::Input(0), PinRef::GateInput { gate: 1, pin: 0 }),
+                    edge(PinRef::Input(1), PinRef::GateInput { gate: 1, pin: 1 }),
+                    edge(PinRef::GateOutput { gate: 0, pin: 0 }, PinRef::Output(0)),
+                    edge(PinRef::GateOutput { gate: 1, pin: 0 }, PinRef::Output(1)),
+                ],
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn nested_components() {
+        let custom = |idx| GateSave {
+            kind: GateType::Custom(idx),
+            width: 1,
+            inputs: 2,
+            state: GateState::default(),
+            pos: [0.0, 0.0],
+            size: [200.0, 120.0],
+            delay: None,
+        };
+        let edge = |from, to| EdgeSave { from, to, delay: None };
+        let input = |label: &str, value| InputSave { label: label.into(), value, width: 1 };
+        let output = |label: &str| OutputSave { label: label.into(), width: 1 };
+
+        // Adds A and B twice over, so S = A ^ B and C = A & B
+        let twice = ComponentSave {
+            name: "Twice".into(),
+            library: None,
+            circuit: CircuitFile {
+                inputs: vec![input("A", 0), input("B", 0)],
+                outputs: vec![output("S"), output("C")],
+                gates: vec![custom(0)],
+                edges: vec![
+                    edge(PinRef::Input(0), PinRef::GateInput { gate: 0, pin: 0 }),
+                    edge(PinRef::Input(1), PinRef::GateInput { gate: 0, pin: 1 }),
+                    edge(PinRef::GateOutput { gate: 0, pin: 0 }, PinRef::Output(0)),
+                    edge(PinRef::GateOutput { gate: 0, pin: 1 }, PinRef::Output(1)),
+                ],
+                ..Default::default()
+            },
+        };
+        let file = CircuitFile {
+            inputs: vec![input("A", 1), input("B", 1)],
+            outputs: vec![output("S"), output("C")],
+            gates: vec![custom(1)],
+            edges: twice.circuit.edges.to_vec(),
+            components: vec![half_adder(), twice],
+            ..Default::default()
+        };
+
+        let file = CircuitFile::from_ron(&file.to_ron()).unwrap();
+        let (mut circuit, interface) = file.to_circuit();
+        circuit.run_until_stable(20).unwrap();
+        assert!(!circuit.get(interface.outputs[0].1));
+        assert!(circuit.get(interface.outputs[1].1));
+    }
+
+    #[test]
+    fn components_only_use_earlier_ones() {
+        let mut file = CircuitFile { components: vec![half_adder()], ..Default::default() };
+        file.components[0].circuit.gates[0].kind = GateType::Custom(0);
+        file.components[0].circuit.gates[0].inputs = 2;
+        assert!(matches!(CircuitFile::from_ron(&file.to_ron()), Err(LoadError::InvalidComponent { gate: 0 })));
+    }
+
+    #[test]
+    fn links_libraries() {
+        let path = std::env::temp_dir().join(format!("logic-sim-library-{}.ron", std::process::id()));
+        let mut library = LibraryFile { components: vec![half_adder()], ..Default::default() };
+        library.save(&path).unwrap();
+
+        let mut components = vec![];
+        assert!(library.import(&path, &mut components).is_empty());
+        // Importing again keeps the components that are already there
+        assert!(library.import(&path, &mut components).is_empty());
+        assert_eq!(components.len(), 1);
+
+        let custom = GateSave {
+            kind: GateType::Custom(0),
+            width: 1,
+            inputs: 2,
+            state: GateState::default(),
+            pos: [0.0, 0.0],
+            size: [200.0, 120.0],
+            delay: None,
+        };
+        let file = CircuitFile {
+            inputs: vec![InputSave { label: "A".into(), value: 1, width: 1 }],
+            gates: vec![custom],
+            edges: vec![
+                EdgeSave { from: PinRef::Input(0), to: PinRef::GateInput { gate: 0, pin: 0 }, delay: None },
+                EdgeSave { from: PinRef::Input(0), to: PinRef::GateInput { gate: 0, pin: 1 }, delay: None },
+            ],
+            components: components.iter().map(ComponentSave::stub).collect(),
+            ..Default::default()
+        };
+        assert!(file.components[0].circuit.gates.is_empty());
+
+        let mut loaded = CircuitFile::from_ron(&file.to_ron()).unwrap();
+        assert!(loaded.link_libraries().unwrap().is_empty());
+        assert_eq!(loaded.components, components);
+
+        // The half adder loses its second input
+        let half_adder = &mut library.components[0].circuit;
+        half_adder.inputs.pop();
+        half_adder.edges.retain(|edge| edge.from != PinRef::Input(1));
+        library.save(&path).unwrap();
+
+        let mut loaded = CircuitFile::from_ron(&file.to_ron()).unwrap();
+        assert_eq!(loaded.link_libraries().unwrap(), vec!["Half adder".to_string()]);
+        assert_eq!(loaded.edges, file.edges[..1]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn imports_skip_taken_names() {
+        let library = LibraryFile { components: vec![half_adder()], ..Default::default() };
+        let mut components = vec![half_adder()];
+        let skipped = library.import(Path::new("library.ron"), &mut components);
+        assert_eq!(skipped, vec!["Half adder".to_string()]);
+        assert_eq!(components.len(), 1);
+    }
+
+    #[test]
+    fn delays_fall_back_to_type_then_default() {
+        let mut delays = Delays::default();
+        delays.set_kind(GateType::Clock(ClockConfig { period: 4, high: 1 }), 3);
+
+        assert_eq!(delays.gate(GateType::Clock(ClockConfig::default()), None), 3);
+        assert_eq!(delays.gate(GateType::Clock(ClockConfig::default()), Some(5)), 5);
+        assert_eq!(delays.gate(GateType::Not, None), 0);
+        assert_eq!(delays.edge(None), 1);
+        assert_eq!(delays.edge(Some(0)), 0);
+    }
+
+    #[test]
+    fn rejects_newer_libraries() {
+        let library = LibraryFile { version: VERSION + 1, ..Default::default() };
+        assert!(matches!(LibraryFile::from_ron(&library.to_ron()), Err(LoadError::UnsupportedVersion(_))));
+    }
+}
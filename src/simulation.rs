@@ -0,0 +1,704 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    time::Duration,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use logic_sim::save::Delays;
+
+use crate::{
+    circuit::{GateType, Resolution, Signal},
+    component::{Library, Subcircuit},
+    constants::Colors,
+    diagnostics::Diagnostics,
+    edge::{Edge, EdgeSignal, HoveredEdge},
+    file::{prompt_closed, FileStatus},
+    gate::{Gate, HoveredGate},
+    history::{Edit, History},
+    node::Node,
+    ui::text_builder,
+};
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Simulation::default())
+            .insert_resource(EventQueue::default())
+            .insert_resource(DelaySettings::default())
+            .insert_resource(Wiring::default())
+            .insert_resource(DriverResolution::default())
+            .insert_resource(TickTimer(Timer::from_seconds(TICK_SECONDS, TimerMode::Repeating)))
+            // Everything that runs on simulated time reads the delta of this frame
+            .add_system_to_stage(CoreStage::PreUpdate, advance_simulation)
+            // Edges and gates spawned during the frame are there once it is over
+            .add_system_to_stage(CoreStage::PostUpdate, update_wiring)
+            .add_system_to_stage(CoreStage::PostUpdate, propagate.after(update_wiring))
+            .add_startup_system(create_simulation_ui)
+            .add_system(interact_simulation_ui)
+            .add_system(simulation_shortcuts.with_run_criteria(prompt_closed))
+            .add_system(edit_delays.with_run_criteria(prompt_closed))
+            .add_system(show_simulation_state);
+    }
+}
+
+/// Length of a simulation tick, the unit of delays
+pub const TICK_SECONDS: f32 = 0.1;
+
+const MIN_SPEED: f32 = 1.0 / 16.0;
+const MAX_SPEED: f32 = 16.0;
+
+/// Maximum number of rounds of gate updates and deliveries in a frame, so that signals looping through edges
+/// without any delay can't freeze the program. What is left is carried on next frame.
+const MAX_ROUNDS: usize = 1000;
+
+/// Clock of the simulation, which can be paused, stepped one tick at a time, or run faster or slower than real time
+#[derive(Resource)]
+pub struct Simulation {
+    pub running: bool,
+    /// How many times faster than real time the simulation runs
+    pub speed: f32,
+    /// Whether a single tick was asked for while paused
+    step: bool,
+    /// Simulated time elapsed during this frame
+    delta: Duration,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self { running: true, speed: 1.0, step: false, delta: Duration::ZERO }
+    }
+}
+
+impl Simulation {
+    /// Simulated time elapsed during this frame, to use instead of [`Time::delta`]
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Pauses the simulation, and advances it by one tick on the next frame
+    pub fn step(&mut self) {
+        self.running = false;
+        self.step = true;
+    }
+
+    /// Halves or doubles the speed
+    fn change_speed(&mut self, faster: bool) {
+        let speed = if faster { self.speed * 2.0 } else { self.speed / 2.0 };
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+}
+
+/// Delays of the circuit, saved with it
+#[derive(Resource, Default)]
+pub struct DelaySettings(pub Delays);
+
+fn ticks(count: u32) -> Duration {
+    Duration::from_secs_f32(TICK_SECONDS) * count
+}
+
+/// Paces the clocks, including those inside custom components
+#[derive(Resource)]
+struct TickTimer(Timer);
+
+/// Where a signal is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Target {
+    /// The node at the end of an edge
+    Edge(Entity),
+    /// An output of a gate, once the gate's delay is over
+    Pin(Entity),
+}
+
+/// Arrival time, order of sending, target, value and cause of a signal
+type Scheduled = (Duration, u64, Target, Signal, u64);
+
+/// Signals travelling through edges and gates, delivered in order of arrival
+#[derive(Resource, Default)]
+pub struct EventQueue {
+    /// Simulated time since the start
+    now: Duration,
+    events: BinaryHeap<Reverse<Scheduled>>,
+    /// Number of signals sent so far, so that those arriving at the same time are delivered in the order they were sent
+    sent: u64,
+    /// Order and value of the last signal sent to each gate output that hasn't arrived yet
+    pending: HashMap<Entity, (u64, Signal)>,
+}
+
+impl EventQueue {
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Schedules a value to arrive after the given delay, returning its order
+    fn schedule(&mut self, target: Target, value: Signal, cause: u64, delay: Duration) -> u64 {
+        let order = self.sent;
+        self.events.push(Reverse((self.now + delay, order, target, value, cause)));
+        self.sent += 1;
+        order
+    }
+
+    /// Sends a value through an edge, to arrive after the given delay
+    fn send(&mut self, edge: Entity, signal: &mut EdgeSignal, value: Signal, cause: u64, delay: Duration) {
+        signal.previous = signal.value;
+        signal.value = value;
+        signal.sent = self.now;
+        signal.arrival = self.now + delay;
+        self.schedule(Target::Edge(edge), value, cause, delay);
+    }
+
+    /// Sets a gate output after the given delay
+    fn send_output(&mut self, pin: Entity, value: Signal, cause: u64, delay: Duration) {
+        let order = self.schedule(Target::Pin(pin), value, cause, delay);
+        self.pending.insert(pin, (order, value));
+    }
+
+    /// Value a gate output will have once the signals sent to it arrive
+    fn pending_output(&self, pin: Entity) -> Option<Signal> {
+        self.pending.get(&pin).map(|&(_, value)| value)
+    }
+
+    /// Next signal that arrived by now, with its target and cause
+    fn pop_arrived(&mut self) -> Option<(Target, Signal, u64)> {
+        let Reverse((arrival, ..)) = self.events.peek()?;
+        if *arrival > self.now {
+            return None;
+        }
+        let Reverse((_, order, target, value, cause)) = self.events.pop()?;
+        if let Target::Pin(pin) = target {
+            if self.pending.get(&pin).map(|&(last, _)| last) == Some(order) {
+                self.pending.remove(&pin);
+            }
+        }
+        Some((target, value, cause))
+    }
+}
+
+/// Tells apart the changes that follow from different stimuli, like an input toggled by hand or a clock tick,
+/// so that nodes that keep changing because of a single one can be reported as oscillating.
+/// Changes follow from the most recent stimulus among the inputs of the gate they come from.
+#[derive(Default)]
+struct Causes {
+    last: u64,
+    /// Stimulus behind the last change of each node, and how many changes of the node followed from it
+    nodes: HashMap<Entity, (u64, u32)>,
+    /// Nodes changed during the last frame that didn't get to be propagated
+    leftover: Vec<Entity>,
+}
+
+impl Causes {
+    fn stimulus(&mut self) -> u64 {
+        self.last += 1;
+        self.last
+    }
+
+    fn of(&self, node: Entity) -> u64 {
+        self.nodes.get(&node).map_or(0, |&(cause, _)| cause)
+    }
+
+    /// Records a change of the node, and returns how many changes followed from the same stimulus
+    fn record(&mut self, node: Entity, cause: u64) -> u32 {
+        let entry = self.nodes.entry(node).or_insert((cause, 0));
+        if entry.0 != cause {
+            *entry = (cause, 0);
+        }
+        entry.1 += 1;
+        entry.1
+    }
+}
+
+/// Edges leaving and reaching each node
+#[derive(Resource, Default)]
+struct Wiring {
+    fanout: HashMap<Entity, Vec<Entity>>,
+    fanin: HashMap<Entity, Vec<Entity>>,
+    /// Nodes whose drivers changed since last frame
+    rewired: Vec<Entity>,
+}
+
+/// How nodes driven by several edges get their value, saved with the circuit
+#[derive(Resource, Default)]
+pub struct DriverResolution(pub Resolution);
+
+fn advance_simulation(mut simulation: ResMut<Simulation>, time: Res<Time>) {
+    simulation.delta = if simulation.running {
+        time.delta().mul_f32(simulation.speed)
+    } else if simulation.step {
+        Duration::from_secs_f32(TICK_SECONDS)
+    } else {
+        Duration::ZERO
+    };
+    simulation.step = false;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SimulationCommand {
+    /// Runs or pauses the simulation
+    Toggle,
+    Step,
+    Slower,
+    Faster,
+    /// Shortens or lengthens the default delay of edges
+    EdgeDelay(bool),
+    /// Shortens or lengthens the default delay of gates
+    GateDelay(bool),
+    /// Switches to the next way of resolving nodes with several drivers
+    Resolution,
+}
+
+impl SimulationCommand {
+    fn apply(self, simulation: &mut Simulation, delays: &mut Delays, resolution: &mut Resolution) {
+        let change = |delay: &mut u32, longer| *delay = if longer { *delay + 1 } else { delay.saturating_sub(1) };
+        match self {
+            SimulationCommand::Toggle => simulation.running = !simulation.running,
+            SimulationCommand::Step => simulation.step(),
+            SimulationCommand::Slower => simulation.change_speed(false),
+            SimulationCommand::Faster => simulation.change_speed(true),
+            SimulationCommand::EdgeDelay(longer) => change(&mut delays.edge, longer),
+            SimulationCommand::GateDelay(longer) => change(&mut delays.gate, longer),
+            SimulationCommand::Resolution => *resolution = resolution.next(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct SimulationButton(SimulationCommand);
+
+/// Text of the run/pause button
+#[derive(Component)]
+struct ToggleText;
+
+/// Text showing a setting, between the buttons that change it
+#[derive(Component, Clone, Copy)]
+enum SettingText {
+    Speed,
+    EdgeDelay,
+    GateDelay,
+    Resolution,
+}
+
+fn create_simulation_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let button_style = Style {
+        size: Size::new(Val::Auto, Val::Px(40.0)),
+        margin: UiRect::all(Val::Px(10.0)),
+        padding: UiRect::horizontal(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(75.0),
+                    top: Val::Px(0.0),
+                    ..default()
+                },
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Colors::UI_BG.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            use SimulationCommand::*;
+            let commands = [
+                (Toggle, "Pause"),
+                (Step, "Step"),
+                (Slower, "-"),
+                (Faster, "+"),
+                (EdgeDelay(false), "-"),
+                (EdgeDelay(true), "+"),
+                (GateDelay(false), "-"),
+                (GateDelay(true), "+"),
+                (SimulationCommand::Resolution, ""),
+            ];
+            for (command, label) in commands {
+                let setting = match command {
+                    Faster => Some(SettingText::Speed),
+                    EdgeDelay(true) => Some(SettingText::EdgeDelay),
+                    GateDelay(true) => Some(SettingText::GateDelay),
+                    _ => None,
+                };
+                if let Some(setting) = setting {
+                    c.spawn((text_builder("", &asset_server), setting));
+                }
+
+                c.spawn((
+                    ButtonBundle {
+                        style: button_style.clone(),
+                        background_color: Colors::OFF.into(),
+                        ..default()
+                    },
+                    SimulationButton(command),
+                ))
+                .with_children(|c| {
+                    let mut text = c.spawn(text_builder(label, &asset_server));
+                    match command {
+                        Toggle => text.insert(ToggleText),
+                        SimulationCommand::Resolution => text.insert(SettingText::Resolution),
+                        _ => &mut text,
+                    };
+                });
+            }
+        });
+}
+
+fn interact_simulation_ui(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &SimulationButton), Changed<Interaction>>,
+    mut simulation: ResMut<Simulation>,
+    mut delays: ResMut<DelaySettings>,
+    mut resolution: ResMut<DriverResolution>,
+) {
+    for (interaction, mut color, &SimulationButton(command)) in &mut query {
+        match *interaction {
+            Interaction::None => *color = Colors::OFF.into(),
+            Interaction::Hovered => *color = Colors::highlighted(false).into(),
+            Interaction::Clicked => {
+                *color = Colors::ON.into();
+                command.apply(&mut simulation, &mut delays.0, &mut resolution.0);
+            }
+        }
+    }
+}
+
+/// P runs or pauses the simulation, N steps it, and the comma and period keys slow it down or speed it up
+fn simulation_shortcuts(
+    keys: Res<Input<KeyCode>>,
+    mut simulation: ResMut<Simulation>,
+    mut delays: ResMut<DelaySettings>,
+    mut resolution: ResMut<DriverResolution>,
+) {
+    let command = if keys.just_pressed(KeyCode::P) {
+        SimulationCommand::Toggle
+    } else if keys.just_pressed(KeyCode::N) {
+        SimulationCommand::Step
+    } else if keys.just_pressed(KeyCode::Comma) {
+        SimulationCommand::Slower
+    } else if keys.just_pressed(KeyCode::Period) {
+        SimulationCommand::Faster
+    } else {
+        return;
+    };
+    command.apply(&mut simulation, &mut delays.0, &mut resolution.0);
+}
+
+/// Gates and edges, along with the ones under the mouse, whose delays can be changed
+#[derive(SystemParam)]
+struct Delayed<'w, 's> {
+    hovered_gate: Res<'w, HoveredGate>,
+    hovered_edge: Res<'w, HoveredEdge>,
+    gates: Query<'w, 's, &'static mut Gate>,
+    signals: Query<'w, 's, &'static mut EdgeSignal>,
+}
+
+/// Page up and page down lengthen or shorten the delay of the gate or edge under the mouse,
+/// or with shift the delay of every gate of the same type
+fn edit_delays(
+    keys: Res<Input<KeyCode>>,
+    delayed: Delayed,
+    mut delays: ResMut<DelaySettings>,
+    mut history: ResMut<History>,
+    mut status: ResMut<FileStatus>,
+    library: Res<Library>,
+) {
+    let change = if keys.just_pressed(KeyCode::PageUp) {
+        1
+    } else if keys.just_pressed(KeyCode::PageDown) {
+        -1
+    } else {
+        return;
+    };
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    let Delayed { hovered_gate, hovered_edge, mut gates, mut signals } = delayed;
+
+    if let Some(entity) = hovered_gate.0 {
+        let Ok(mut gate) = gates.get_mut(entity) else { return };
+
+        if shift {
+            let delay = delays.0.gate(gate.kind, None).saturating_add_signed(change);
+            delays.0.set_kind(gate.kind, delay);
+            status.0 = format!("Delay of {} gates: {delay} ticks", library.name(gate.kind));
+            // Gates with the default delay use the new one from now on
+            gate.set_changed();
+            return;
+        }
+
+        let from = gate.delay;
+        let to = Some(delays.0.gate(gate.kind, from).saturating_add_signed(change));
+        if to != from {
+            history.push(Edit::SetDelay { target: entity, from, to });
+            gate.delay = to;
+        }
+        status.0 = format!("Delay of this gate: {} ticks", delays.0.gate(gate.kind, to));
+    } else if let Some(entity) = hovered_edge.0 {
+        let Ok(mut signal) = signals.get_mut(entity) else { return };
+
+        let from = signal.delay;
+        let to = Some(delays.0.edge(from).saturating_add_signed(change));
+        if to != from {
+            history.push(Edit::SetDelay { target: entity, from, to });
+            signal.delay = to;
+        }
+        status.0 = format!("Delay of this edge: {} ticks", delays.0.edge(to));
+    }
+}
+
+fn show_simulation_state(
+    simulation: Res<Simulation>,
+    delays: Res<DelaySettings>,
+    resolution: Res<DriverResolution>,
+    mut toggle: Query<&mut Text, With<ToggleText>>,
+    mut settings: Query<(&mut Text, &SettingText), Without<ToggleText>>,
+) {
+    toggle.single_mut().sections[0].value = if simulation.running { "Pause" } else { "Run" }.to_string();
+    for (mut text, setting) in &mut settings {
+        text.sections[0].value = match setting {
+            SettingText::Speed => format!("x{}", simulation.speed),
+            SettingText::EdgeDelay => format!("Wires {}", delays.0.edge),
+            SettingText::GateDelay => format!("Gates {}", delays.0.gate),
+            SettingText::Resolution => resolution.0.as_str().to_string(),
+        };
+    }
+}
+
+/// Rebuilds the fanout and fanin of the nodes when edges are added or removed
+fn update_wiring(
+    mut wiring: ResMut<Wiring>,
+    edges: Query<(Entity, &Edge)>,
+    added: Query<(), Added<Edge>>,
+    removed: RemovedComponents<Edge>,
+) {
+    if added.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+
+    let old = std::mem::take(&mut wiring.fanin);
+    wiring.fanout.clear();
+    for (entity, edge) in edges.iter() {
+        wiring.fanout.entry(edge.from).or_default().push(entity);
+        wiring.fanin.entry(edge.to).or_default().push(entity);
+    }
+
+    let mut rewired: Vec<_> = old.keys().chain(wiring.fanin.keys()).copied().collect();
+    rewired.sort();
+    rewired.dedup();
+    rewired.retain(|node| old.get(node) != wiring.fanin.get(node));
+    wiring.rewired = rewired;
+}
+
+/// Value of a node of the given width from what arrived through the edges driving it, floating if nothing did.
+/// Conflicts are reported to the diagnostics.
+fn resolve_drivers(
+    node: Entity,
+    width: u8,
+    drivers: &[Entity],
+    edges: &Query<(&Edge, &mut EdgeSignal)>,
+    resolution: Resolution,
+    diagnostics: &mut ResMut<Diagnostics>,
+) -> Signal {
+    let arrived: Vec<_> = drivers.iter().filter_map(|&edge| edges.get(edge).ok()?.1.arrived).collect();
+    let resolved = resolution.resolve(&arrived, width);
+
+    let conflict = resolution.conflicts(&arrived);
+    if conflict != diagnostics.conflicts.contains(&node) {
+        match conflict {
+            true => diagnostics.conflicts.insert(node),
+            false => diagnostics.conflicts.remove(&node),
+        };
+    }
+    resolved
+}
+
+/// Signals in flight, what caused them, and how they travel
+#[derive(SystemParam)]
+struct Propagation<'w, 's> {
+    queue: ResMut<'w, EventQueue>,
+    causes: Local<'s, Causes>,
+    timer: ResMut<'w, TickTimer>,
+    wiring: ResMut<'w, Wiring>,
+    delays: Res<'w, DelaySettings>,
+    resolution: Res<'w, DriverResolution>,
+}
+
+/// Edges, nodes and gates the signals go through
+#[derive(SystemParam)]
+struct Simulated<'w, 's> {
+    edges: Query<'w, 's, (&'static Edge, &'static mut EdgeSignal)>,
+    added_edges: Query<'w, 's, Entity, Added<Edge>>,
+    nodes: Query<'w, 's, (Entity, &'static mut Node)>,
+    parents: Query<'w, 's, &'static Parent, With<Node>>,
+    gates: Query<'w, 's, (Entity, &'static mut Gate, Option<&'static mut Subcircuit>)>,
+}
+
+/// Moves the simulation forward: the new value of a node is sent through the edges leaving it, and updates the gate
+/// it is an input of. Only what changed is looked at, instead of every gate and edge.
+fn propagate(
+    simulation: Res<Simulation>,
+    mut diagnostics: ResMut<Diagnostics>,
+    propagation: Propagation,
+    simulated: Simulated,
+) {
+    let Propagation { mut queue, mut causes, mut timer, mut wiring, delays, resolution } = propagation;
+    let Simulated { mut edges, added_edges, mut nodes, parents, mut gates } = simulated;
+
+    queue.now += simulation.delta();
+    timer.0.tick(simulation.delta());
+
+    // Nodes changed by hand, and gates that were added or reconfigured since last frame, each with its own cause
+    let mut changed = std::mem::take(&mut causes.leftover);
+    for (node, _) in nodes.iter_mut().filter(|(_, node)| node.is_changed()) {
+        let cause = causes.stimulus();
+        causes.record(node, cause);
+        changed.push(node);
+    }
+    let mut dirty: HashMap<_, _> = HashMap::new();
+    for (gate, ..) in gates.iter_mut().filter(|(_, gate, _)| gate.is_changed()) {
+        dirty.insert(gate, causes.stimulus());
+    }
+
+    for _ in 0..timer.0.times_finished_this_tick() {
+        for (entity, mut gate, subcircuit) in &mut gates {
+            if let GateType::Clock(_) = gate.kind {
+                let mut state = gate.state;
+                gate.kind.tick(&mut state);
+                if state != gate.state {
+                    gate.state = state;
+                    dirty.insert(entity, causes.stimulus());
+                }
+            }
+            // The component settles again once its clocks moved
+            if let Some(mut subcircuit) = subcircuit {
+                if subcircuit.circuit.tick_clocks() {
+                    dirty.insert(entity, causes.stimulus());
+                }
+            }
+        }
+    }
+
+    // Nodes that lost or gained a driver, or that have several when the resolution changes, are resolved again
+    let mut rewired = std::mem::take(&mut wiring.rewired);
+    if resolution.is_changed() {
+        rewired.extend(wiring.fanin.iter().filter(|(_, drivers)| drivers.len() > 1).map(|(&node, _)| node));
+    }
+    for node in rewired {
+        let drivers = wiring.fanin.get(&node).map_or(&[][..], Vec::as_slice);
+        let Ok((_, mut node_value)) = nodes.get_mut(node) else { continue };
+        let resolved = resolve_drivers(node, node_value.width, drivers, &edges, resolution.0, &mut diagnostics);
+        if resolved != node_value.value {
+            node_value.value = resolved;
+            let cause = causes.stimulus();
+            causes.record(node, cause);
+            changed.push(node);
+        }
+    }
+
+    // New edges carry the value of their source right away
+    for entity in added_edges.iter() {
+        let Ok((&Edge { from, .. }, mut signal)) = edges.get_mut(entity) else { continue };
+        let Ok((_, node)) = nodes.get(from) else { continue };
+        let delay = ticks(delays.0.edge(signal.delay));
+        queue.send(entity, &mut signal, node.value, causes.stimulus(), delay);
+    }
+
+    for _ in 0..MAX_ROUNDS {
+        for node in changed.drain(..) {
+            let Ok((_, &Node { value, .. })) = nodes.get(node) else { continue };
+            let cause = causes.of(node);
+
+            for &edge in wiring.fanout.get(&node).into_iter().flatten() {
+                let Ok((_, mut signal)) = edges.get_mut(edge) else { continue };
+                if signal.value != value {
+                    let delay = ticks(delays.0.edge(signal.delay));
+                    queue.send(edge, &mut signal, value, cause, delay);
+                }
+            }
+
+            // Pins of gates are their children
+            let Ok(parent) = parents.get(node) else { continue };
+            if let Ok((gate, ..)) = gates.get(parent.get()) {
+                let latest = dirty.entry(gate).or_insert(cause);
+                *latest = cause.max(*latest);
+            }
+        }
+
+        for (gate, cause) in dirty.drain() {
+            let Ok((_, mut gate, subcircuit)) = gates.get_mut(gate) else { continue };
+            // A node may be missing for a frame while its gate is being deleted
+            let Ok(inputs) = gate
+                .inputs
+                .iter()
+                .map(|&id| nodes.get(id).map(|(_, node)| node.value))
+                .collect::<Result<Vec<_>, _>>() else { continue };
+
+            let (outputs, state) = gate.evaluate(subcircuit.map(|s| s.into_inner()), &inputs);
+            // Only touching what changed keeps the change trackers meaningful
+            if state != gate.state {
+                gate.state = state;
+            }
+            let delay = ticks(delays.0.gate(gate.kind, gate.delay));
+            for (&output, value) in gate.outputs.iter().zip(outputs) {
+                let Ok((_, mut node)) = nodes.get_mut(output) else { continue };
+                if delay.is_zero() {
+                    if node.value != value {
+                        node.value = value;
+                        changed.push(output);
+                        let count = causes.record(output, cause);
+                        if count > diagnostics.settle_limit && !diagnostics.oscillating.contains(&output) {
+                            diagnostics.oscillating.insert(output);
+                        }
+                    }
+                } else if queue.pending_output(output).unwrap_or(node.value) != value {
+                    queue.send_output(output, value, cause, delay);
+                }
+            }
+        }
+
+        if changed.is_empty() {
+            while let Some((target, value, cause)) = queue.pop_arrived() {
+                let (to, value) = match target {
+                    Target::Edge(edge) => {
+                        let Ok((&Edge { to, .. }, mut signal)) = edges.get_mut(edge) else { continue };
+                        signal.arrived = Some(value);
+
+                        // Nodes driven by several edges combine what arrived through each of them
+                        let drivers = wiring.fanin.get(&to).map_or(&[][..], Vec::as_slice);
+                        if drivers.len() > 1 {
+                            let Ok((_, node)) = nodes.get(to) else { continue };
+                            (to, resolve_drivers(to, node.width, drivers, &edges, resolution.0, &mut diagnostics))
+                        } else {
+                            (to, value)
+                        }
+                    }
+                    Target::Pin(pin) => (pin, value),
+                };
+                let Ok((_, mut node)) = nodes.get_mut(to) else { continue };
+                if node.value != value {
+                    node.value = value;
+                    changed.push(to);
+                    let count = causes.record(to, cause);
+                    if count > diagnostics.settle_limit && !diagnostics.oscillating.contains(&to) {
+                        diagnostics.oscillating.insert(to);
+                    }
+                }
+            }
+        }
+
+        if changed.is_empty() {
+            break;
+        }
+    }
+
+    // Nothing oscillates once every signal arrived
+    if changed.is_empty() && queue.events.is_empty() {
+        causes.nodes.clear();
+        if !diagnostics.oscillating.is_empty() {
+            diagnostics.oscillating.clear();
+        }
+    }
+    causes.leftover = changed;
+}
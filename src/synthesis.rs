@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use logic_sim::{
+    expression::Expr,
+    truth_table::{TooManyInputs, TruthTable},
+};
+
+use crate::{
+    circuit::{GateState, GateType, Signal, VARIADIC_INPUTS},
+    component::Library,
+    edge::{Edge, EdgeBundle, EdgeSignal},
+    file::{Boundary, CircuitSnapshot, FileStatus},
+    gate::{snap_vec, Gate, GateBundle, GateSpawner, Selection, GATE_SIZE, GRID_SIZE},
+    history::{EdgeRecord, Edit, GateRecord, History, PanelNodeRecord},
+    node::Node,
+    ui::{next_label, spawn_panel_node, InputNodeMarker, NodeLabel, OutputNodeMarker, Panel, PanelRootMarker},
+};
+
+pub struct SynthesisPlugin;
+
+impl Plugin for SynthesisPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BuildExpression>()
+            .add_event::<MinimizeSelection>()
+            .add_system(build_expression)
+            .add_system(minimize_selection);
+    }
+}
+
+/// Horizontal distance between the columns of gates
+const COLUMN_SPACING: f32 = 2.0 * GATE_SIZE.x;
+
+/// Vertical space between gates of the same column
+const ROW_GAP: f32 = 40.0;
+
+/// Parses a boolean expression and builds it out of gates, fed by the input nodes named after its variables
+pub struct BuildExpression(pub String);
+
+/// Replaces the selected gates with the minimal sums of products of their outputs, if their inputs have at most that
+/// many bits
+pub struct MinimizeSelection(pub u32);
+
+/// Gate computing an expression, with the expressions feeding its inputs, or `None` for variables and constants.
+/// Inverted operations become a single inverted gate.
+fn gate_of(expr: &Expr) -> Option<(GateType, &[Expr])> {
+    Some(match expr {
+        Expr::Const(_) | Expr::Var(_) => return None,
+        Expr::Not(inner) => match &**inner {
+            Expr::And(operands) => (GateType::Nand, operands),
+            Expr::Or(operands) => (GateType::Nor, operands),
+            Expr::Xor(operands) => (GateType::Xnor, operands),
+            _ => (GateType::Not, std::slice::from_ref(&**inner)),
+        },
+        Expr::And(operands) => (GateType::And, operands),
+        Expr::Or(operands) => (GateType::Or, operands),
+        Expr::Xor(operands) => (GateType::Xor, operands),
+    })
+}
+
+/// Splits chains with more operands than a gate has inputs into chains of chains
+fn narrow(expr: Expr) -> Expr {
+    let max = *VARIADIC_INPUTS.end();
+    let split = |operands: Vec<Expr>, group: fn(Vec<Expr>) -> Expr| {
+        let mut operands: Vec<_> = operands.into_iter().map(narrow).collect();
+        while operands.len() > max {
+            let chunks = operands.chunks(max).map(|chunk| match chunk {
+                [single] => single.clone(),
+                chunk => group(chunk.to_vec()),
+            });
+            operands = chunks.collect();
+        }
+        group(operands)
+    };
+    match expr {
+        Expr::Not(inner) => Expr::Not(Box::new(narrow(*inner))),
+        Expr::And(operands) => split(operands, Expr::And),
+        Expr::Or(operands) => split(operands, Expr::Or),
+        Expr::Xor(operands) => split(operands, Expr::Xor),
+        expr => expr,
+    }
+}
+
+/// Number of gates between the inputs and the output of the expression
+fn depth(expr: &Expr) -> usize {
+    gate_of(expr).map_or(0, |(_, operands)| 1 + operands.iter().map(depth).max().unwrap_or(0))
+}
+
+/// Spawns the gates of an expression from left to right, a column for each level, and records the edits
+struct Synthesis<'a, 'w, 's> {
+    commands: &'a mut Commands<'w, 's>,
+    asset_server: &'a Res<'a, AssetServer>,
+    library: &'a Library,
+    /// Nodes driving the variables and constants, by name
+    sources: HashMap<String, Entity>,
+    /// Position of the first column, and top of the free space below the gates spawned so far
+    left: f32,
+    top: f32,
+    edits: Vec<Edit>,
+}
+
+impl Synthesis<'_, '_, '_> {
+    fn connect(&mut self, from: Entity, to: Entity) {
+        let edge = self.commands.spawn(EdgeBundle::new(from, to)).id();
+        self.edits.push(Edit::AddEdge(EdgeRecord { edge, from, to, delay: None }));
+    }
+
+    /// Spawns the gates computing the expression, and returns the node carrying its value along with the height
+    /// of the gate driving it, if it isn't a source
+    fn build(&mut self, expr: &Expr) -> (Entity, Option<f32>) {
+        let Some((kind, operands)) = gate_of(expr) else { return (self.sources[&source_label(expr)], None) };
+
+        let built: Vec<_> = operands.iter().map(|operand| self.build(operand)).collect();
+        let bundle = GateBundle::new(self.asset_server, self.library, kind, GATE_SIZE).inputs(operands.len());
+        let size = bundle.size;
+
+        // Gates fed by other gates sit between them, the others on a new row
+        let fed: Vec<_> = built.iter().filter_map(|&(_, y)| y).collect();
+        let y = match fed.len() {
+            0 => {
+                let y = self.top - size.y / 2.0;
+                self.top -= size.y + ROW_GAP;
+                y
+            }
+            count => fed.iter().sum::<f32>() / count as f32,
+        };
+        let pos = snap_vec(Vec2::new(self.left + (depth(expr) - 1) as f32 * COLUMN_SPACING, y), GRID_SIZE);
+
+        let (gate, inputs, outputs) = bundle.pos(pos).spawn(self.commands);
+        self.edits.push(Edit::AddGate(GateRecord {
+            gate,
+            inputs: inputs.clone(),
+            outputs: outputs.clone(),
+            kind,
+            width: 1,
+            state: GateState::default(),
+            pos,
+            size,
+            delay: None,
+        }));
+        for (&(from, _), &to) in built.iter().zip(&inputs) {
+            self.connect(from, to);
+        }
+        (outputs[0], Some(pos.y))
+    }
+}
+
+/// Constants are driven by input nodes named after them, which can't be toggled
+fn source_label(expr: &Expr) -> String {
+    match expr {
+        Expr::Const(constant) => (*constant as u8).to_string(),
+        Expr::Var(name) => name.clone(),
+        _ => unreachable!("only variables and constants are sources"),
+    }
+}
+
+/// Whether an existing input node can drive the source it is named after. A node named after a constant must hold it,
+/// since it could have been saved with another value.
+fn can_drive(node: &Node, label: &NodeLabel) -> bool {
+    label.constant().is_none_or(|value| !node.is_bus() && node.value == value)
+}
+
+/// Labels of the constants used in the expression, which have no variable to come from
+fn constants(expr: &Expr, found: &mut Vec<String>) {
+    match expr {
+        Expr::Const(_) if !found.contains(&source_label(expr)) => found.push(source_label(expr)),
+        Expr::Not(inner) => constants(inner, found),
+        Expr::And(operands) | Expr::Or(operands) | Expr::Xor(operands) => {
+            operands.iter().for_each(|operand| constants(operand, found))
+        }
+        _ => {}
+    }
+}
+
+/// Spawns input nodes named after the sources that don't have one yet. Constants hold their value, variables start
+/// low.
+fn spawn_sources(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    (root, children): (Entity, Option<&Children>),
+    names: Vec<String>,
+    sources: &mut HashMap<String, Entity>,
+    edits: &mut Vec<Edit>,
+) {
+    let mut index = children.map_or(0, |children| children.len());
+    for name in names {
+        if sources.contains_key(&name) {
+            continue;
+        }
+        let value = Signal::known((name == "1") as u64);
+        let node = Node { value, width: 1 };
+        let node = spawn_panel_node(commands, Panel::Input, root, asset_server, None, name.clone(), node);
+        edits.push(Edit::AddPanelNode(PanelNodeRecord {
+            panel: Panel::Input,
+            node,
+            index,
+            label: name.clone(),
+            value,
+            width: 1,
+            edges: vec![],
+        }));
+        index += 1;
+        sources.insert(name, node);
+    }
+}
+
+/// Everything needed to spawn gates and panel nodes, record them as a single edit, and report what was built
+#[derive(SystemParam)]
+struct Builder<'w, 's> {
+    spawner: GateSpawner<'w, 's>,
+    history: ResMut<'w, History>,
+    status: ResMut<'w, FileStatus>,
+    roots: Query<'w, 's, (Entity, &'static PanelRootMarker, Option<&'static Children>)>,
+}
+
+/// Builds the expression in the middle of the screen, with a new output node. Input nodes are reused when their label
+/// matches a variable or a constant they hold, and added otherwise.
+fn build_expression(
+    mut events: EventReader<BuildExpression>,
+    builder: Builder,
+    inputs: Query<(Entity, &Node, &NodeLabel), With<InputNodeMarker>>,
+    outputs: Query<&NodeLabel, With<OutputNodeMarker>>,
+    camera: Query<&Transform, With<Camera>>,
+) {
+    let Builder { spawner, mut history, mut status, roots } = builder;
+    let GateSpawner { mut commands, asset_server, library } = spawner;
+    for BuildExpression(text) in events.iter() {
+        let expr = match Expr::parse(text) {
+            Ok(expr) => narrow(expr),
+            Err(e) => {
+                status.0 = format!("Could not parse {text}: {e}");
+                continue;
+            }
+        };
+
+        let mut names: Vec<_> = expr.variables().into_iter().map(str::to_string).collect();
+        constants(&expr, &mut names);
+
+        let existing: HashMap<_, _> =
+            inputs.iter().map(|(entity, node, label)| (label.0.as_str(), (entity, node, label))).collect();
+        let is_bus = |name: &&String| existing.get(name.as_str()).is_some_and(|(_, node, _)| node.is_bus());
+        if let Some(bus) = names.iter().find(is_bus) {
+            status.0 = format!("Could not build {text}: the input {bus} is a bus");
+            continue;
+        }
+        let is_wrong =
+            |name: &&String| existing.get(name.as_str()).is_some_and(|(_, node, label)| !can_drive(node, label));
+        if let Some(constant) = names.iter().find(is_wrong) {
+            status.0 = format!("Could not build {text}: the input {constant} doesn't hold {constant}");
+            continue;
+        }
+
+        let (input_root, _, input_children) = roots.iter().find(|(_, root, _)| root.0 == Panel::Input).unwrap();
+        let (output_root, _, output_children) = roots.iter().find(|(_, root, _)| root.0 == Panel::Output).unwrap();
+        let mut edits = vec![];
+
+        let mut sources: HashMap<_, _> = names
+            .iter()
+            .filter_map(|name| existing.get(name.as_str()).map(|&(entity, _, _)| (name.clone(), entity)))
+            .collect();
+        spawn_sources(&mut commands, &asset_server, (input_root, input_children), names, &mut sources, &mut edits);
+
+        let center = camera.single().translation.truncate();
+        let columns = depth(&expr).max(1);
+        let mut synthesis = Synthesis {
+            commands: &mut commands,
+            asset_server: &asset_server,
+            library: &library,
+            sources,
+            left: center.x - (columns - 1) as f32 * COLUMN_SPACING / 2.0,
+            top: center.y + GATE_SIZE.y,
+            edits,
+        };
+        let (result, _) = synthesis.build(&expr);
+
+        let used: Vec<_> = outputs.iter().map(|label| label.0.as_str()).collect();
+        let label = next_label(Panel::Output, &used);
+        let value = Signal::floating(1);
+        let node = Node { value, width: 1 };
+        let output =
+            spawn_panel_node(synthesis.commands, Panel::Output, output_root, &asset_server, None, label.clone(), node);
+        synthesis.edits.push(Edit::AddPanelNode(PanelNodeRecord {
+            panel: Panel::Output,
+            node: output,
+            index: output_children.map_or(0, |children| children.len()),
+            label: label.clone(),
+            value,
+            width: 1,
+            edges: vec![],
+        }));
+        synthesis.connect(result, output);
+
+        let gates = synthesis.edits.iter().filter(|edit| matches!(edit, Edit::AddGate(_))).count();
+        history.push(Edit::Batch(synthesis.edits));
+        status.0 = format!("Built {text} with {gates} gates into {label}");
+    }
+}
+
+/// Rebuilds the selected gates as the minimal sums of products of their outputs, fed by the same nodes and feeding the
+/// same ones. Only gates without a state that always drive their outputs can be replaced this way, since the new
+/// gates drive them in every case.
+fn minimize_selection(
+    mut events: EventReader<MinimizeSelection>,
+    builder: Builder,
+    mut selection: ResMut<Selection>,
+    snapshot: CircuitSnapshot,
+    gates: Query<(&Gate, &Transform)>,
+    edges: Query<(Entity, &Edge, &EdgeSignal)>,
+    inputs: Query<(Entity, &Node, &NodeLabel), With<InputNodeMarker>>,
+) {
+    let Builder { spawner, mut history, mut status, roots } = builder;
+    let GateSpawner { mut commands, asset_server, library } = spawner;
+    for &MinimizeSelection(limit) in events.iter() {
+        if selection.0.is_empty() {
+            status.0 = "Select the gates to minimize with shift-click".to_string();
+            continue;
+        }
+
+        let (file, Boundary { inputs: drivers, outputs: replaced }) = snapshot.selection_to_file(&selection.0);
+        let (circuit, interface) = file.to_circuit();
+        if let Some((_, gate)) = circuit.gates().find(|(_, gate)| gate.kind.is_sequential()) {
+            status.0 = format!("Could not minimize the selection: its {} keeps a state", gate.kind.as_str());
+            continue;
+        }
+        if circuit.gates().any(|(_, gate)| gate.kind == GateType::TriState) {
+            status.0 = "Could not minimize the selection: its tri-state buffers leave outputs floating".to_string();
+            continue;
+        }
+        if replaced.is_empty() {
+            status.0 = "The selection doesn't feed anything outside of it".to_string();
+            continue;
+        }
+
+        let table = match TruthTable::generate(&circuit, &interface, limit) {
+            Ok(table) => table,
+            Err(TooManyInputs { bits, limit }) => {
+                status.0 = format!("The selection has {bits} input bits, raise the limit of {limit} to minimize it");
+                continue;
+            }
+        };
+        // Unlike in the truth table panel, undefined outputs can't be left to the minimization
+        let undefined = table.outputs.iter().enumerate().find(|&(output, _)| {
+            table.rows.iter().any(|row| !row.outputs[output].is_known())
+        });
+        if let Some((_, column)) = undefined {
+            status.0 = format!("Could not minimize the selection: {} is sometimes floating or unknown", column.name);
+            continue;
+        }
+        let exprs = match (0..table.outputs.len()).map(|output| table.minimize(output)).collect::<Result<Vec<_>, _>>()
+        {
+            Ok(exprs) => exprs.into_iter().map(narrow).collect::<Vec<_>>(),
+            Err(e) => {
+                status.0 = format!("Could not minimize the selection: {e}");
+                continue;
+            }
+        };
+        let mut names = vec![];
+        exprs.iter().for_each(|expr| constants(expr, &mut names));
+        let constant_nodes: Vec<_> = inputs.iter().filter(|(_, _, label)| names.contains(&label.0)).collect();
+        if let Some((_, _, label)) = constant_nodes.iter().find(|(_, node, label)| !can_drive(node, label)) {
+            status.0 = format!("Could not minimize the selection: the input {0} doesn't hold {0}", label.0);
+            continue;
+        }
+
+        // Nodes outside of the selection fed by each output, before their edges go away with the gates
+        let selected: Vec<_> = gates.iter_many(&selection.0).collect();
+        let inside = |node: &Entity| selected.iter().any(|(gate, _)| gate.inputs.contains(node));
+        let sinks: Vec<Vec<_>> = replaced
+            .iter()
+            .map(|&output| {
+                let outside = edges.iter().filter(|(_, edge, _)| edge.from == output && !inside(&edge.to));
+                outside.map(|(_, edge, _)| edge.to).collect()
+            })
+            .collect();
+
+        let mut edits = vec![];
+        let mut removed = vec![];
+        for &entity in &selection.0 {
+            let Ok((gate, transform)) = gates.get(entity) else { continue };
+            let is_pin = |node: &Entity| gate.outputs.contains(node) || gate.inputs.contains(node);
+            // Edges between two selected gates are respawned once, with the gate undone last
+            let attached: Vec<_> = edges
+                .iter()
+                .filter(|(edge, Edge { from, to }, _)| !removed.contains(edge) && (is_pin(from) || is_pin(to)))
+                .map(|(edge, &Edge { from, to }, signal)| EdgeRecord { edge, from, to, delay: signal.delay })
+                .collect();
+            for edge in &attached {
+                commands.entity(edge.edge).despawn();
+                removed.push(edge.edge);
+            }
+            commands.entity(entity).despawn_recursive();
+            let gate = gate.record(entity, transform.translation.truncate());
+            edits.push(Edit::RemoveGate { gate, edges: attached });
+        }
+
+        let mut sources: HashMap<_, _> =
+            interface.inputs.iter().map(|(name, _)| name.clone()).zip(drivers).collect();
+        for (entity, _, label) in constant_nodes {
+            sources.insert(label.0.clone(), entity);
+        }
+        let (input_root, _, input_children) = roots.iter().find(|(_, root, _)| root.0 == Panel::Input).unwrap();
+        spawn_sources(&mut commands, &asset_server, (input_root, input_children), names, &mut sources, &mut edits);
+
+        // The new gates start where the selection did
+        let corner = |(gate, transform): &(&Gate, &Transform)| {
+            let pos = transform.translation.truncate();
+            Vec2::new(pos.x - gate.size.x / 2.0, pos.y + gate.size.y / 2.0)
+        };
+        let left = selected.iter().map(corner).map(|corner| corner.x).fold(f32::INFINITY, f32::min);
+        let top = selected.iter().map(corner).map(|corner| corner.y).fold(f32::NEG_INFINITY, f32::max);
+        let mut synthesis = Synthesis {
+            commands: &mut commands,
+            asset_server: &asset_server,
+            library: &library,
+            sources,
+            left: left + GATE_SIZE.x / 2.0,
+            top,
+            edits,
+        };
+        for (expr, sinks) in exprs.iter().zip(sinks) {
+            let (result, _) = synthesis.build(expr);
+            for sink in sinks {
+                synthesis.connect(result, sink);
+            }
+        }
+
+        let added = synthesis.edits.iter().filter(|edit| matches!(edit, Edit::AddGate(_))).count();
+        history.push(Edit::Batch(synthesis.edits));
+        status.0 = format!("Replaced {} gates with {added}", selected.len());
+        selection.0.clear();
+    }
+}
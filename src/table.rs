@@ -0,0 +1,331 @@
+use std::path::PathBuf;
+
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use logic_sim::{
+    minimize::KarnaughMap,
+    truth_table::{TooManyInputs, TruthTable},
+};
+
+use crate::{
+    camera::Scrollable,
+    circuit::Signal,
+    constants::Colors,
+    file::{CircuitSnapshot, FileCommand, FileStatus},
+    synthesis::MinimizeSelection,
+    ui::{button_style, text_builder},
+};
+
+pub struct TablePlugin;
+
+impl Plugin for TablePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TablePanel { input_limit: 8, table: None, karnaugh: None })
+            .add_event::<ExportTruthTable>()
+            .add_startup_system(create_table_ui)
+            .add_system(interact_table_ui)
+            .add_system(show_truth_table)
+            .add_system(scroll_truth_table)
+            .add_system(export_truth_table);
+    }
+}
+
+/// Number of input bits a truth table can be asked for, each one doubling its length. Every row is settled and
+/// shown at once, so longer tables would freeze the editor.
+const INPUT_LIMITS: std::ops::RangeInclusive<u32> = 1..=10;
+
+/// Height of the part of the panel the rows scroll in
+const TABLE_HEIGHT: f32 = 300.0;
+
+/// How far one line of scrolling moves the rows
+const SCROLL_STEP: f32 = 30.0;
+
+/// Writes the last truth table to a CSV file
+pub struct ExportTruthTable(pub PathBuf);
+
+/// The truth table shown in the panel, if one was generated
+#[derive(Resource)]
+struct TablePanel {
+    /// Largest number of input bits a table is generated for
+    input_limit: u32,
+    table: Option<TruthTable>,
+    /// Output shown as a Karnaugh map along with its minimal sum of products, instead of the rows
+    karnaugh: Option<usize>,
+}
+
+#[derive(Component, Clone, Copy)]
+enum TableButton {
+    Generate,
+    /// Lowers or raises the input limit
+    Limit(bool),
+    Export,
+    /// Goes through the Karnaugh maps of the outputs, then back to the rows
+    Karnaugh,
+    /// Rebuilds the selected gates from the minimal sums of their outputs
+    Minimize,
+}
+
+#[derive(Component)]
+struct InputLimitText;
+
+/// Part of the panel the rows scroll in
+#[derive(Component)]
+struct TableView;
+
+/// Rows of the table, moved up and down within the view
+#[derive(Component)]
+struct TableRows;
+
+fn create_table_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(75.0),
+                    bottom: Val::Px(70.0),
+                    ..default()
+                },
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            background_color: Colors::UI_BG.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            c.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexStart,
+                    max_size: Size::new(Val::Undefined, Val::Px(TABLE_HEIGHT)),
+                    overflow: Overflow::Hidden,
+                    ..default()
+                },
+                ..default()
+            })
+            .insert((TableView, Scrollable, Interaction::default()))
+            .with_children(|c| {
+                c.spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            flex_shrink: 0.0,
+                            padding: UiRect::top(Val::Px(10.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    TableRows,
+                ));
+            });
+
+            c.spawn(NodeBundle { style: Style { align_items: AlignItems::Center, ..default() }, ..default() })
+                .with_children(|c| {
+                    let buttons = [
+                        (TableButton::Generate, "Truth table"),
+                        (TableButton::Limit(false), "-"),
+                        (TableButton::Limit(true), "+"),
+                        (TableButton::Export, "CSV"),
+                        (TableButton::Karnaugh, "K-map"),
+                        (TableButton::Minimize, "Minimize"),
+                    ];
+                    for (button, label) in buttons {
+                        if let TableButton::Limit(true) = button {
+                            c.spawn((text_builder("", &asset_server), InputLimitText));
+                        }
+                        c.spawn((
+                            ButtonBundle {
+                                style: button_style(),
+                                background_color: Colors::OFF.into(),
+                                ..default()
+                            },
+                            button,
+                        ))
+                        .with_children(|c| {
+                            c.spawn(text_builder(label, &asset_server));
+                        });
+                    }
+                });
+        });
+}
+
+fn interact_table_ui(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &TableButton), Changed<Interaction>>,
+    mut panel: ResMut<TablePanel>,
+    mut status: ResMut<FileStatus>,
+    mut file_commands: EventWriter<FileCommand>,
+    mut minimize: EventWriter<MinimizeSelection>,
+    snapshot: CircuitSnapshot,
+) {
+    for (interaction, mut color, &button) in &mut query {
+        match *interaction {
+            Interaction::None => *color = Colors::OFF.into(),
+            Interaction::Hovered => *color = Colors::highlighted(false).into(),
+            Interaction::Clicked => {
+                *color = Colors::ON.into();
+                match button {
+                    TableButton::Generate => {
+                        let (circuit, interface) = snapshot.to_file().to_circuit();
+                        match TruthTable::generate(&circuit, &interface, panel.input_limit) {
+                            Ok(table) => {
+                                status.0 = format!("Truth table of {} rows", table.rows.len());
+                                panel.table = Some(table);
+                                panel.karnaugh = None;
+                            }
+                            Err(TooManyInputs { bits, limit }) => {
+                                status.0 = format!(
+                                    "The inputs have {bits} bits, raise the limit of {limit} to list every combination"
+                                );
+                            }
+                        }
+                    }
+                    TableButton::Limit(more) => {
+                        let limit = if more { panel.input_limit + 1 } else { panel.input_limit - 1 };
+                        panel.input_limit = limit.clamp(*INPUT_LIMITS.start(), *INPUT_LIMITS.end());
+                    }
+                    TableButton::Export => file_commands.send(FileCommand::ExportTruthTable),
+                    TableButton::Karnaugh => {
+                        let outputs = panel.table.as_ref().map_or(0, |table| table.outputs.len());
+                        panel.karnaugh = match panel.karnaugh {
+                            None if outputs > 0 => Some(0),
+                            Some(output) if output + 1 < outputs => Some(output + 1),
+                            _ => None,
+                        };
+                    }
+                    TableButton::Minimize => minimize.send(MinimizeSelection(panel.input_limit)),
+                }
+            }
+        }
+    }
+}
+
+/// Lists the rows under a header of names, each column as wide as its name or its values
+fn show_truth_table(
+    mut commands: Commands,
+    panel: Res<TablePanel>,
+    mut rows: Query<(Entity, &mut Style), With<TableRows>>,
+    mut limit: Query<&mut Text, With<InputLimitText>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !panel.is_changed() {
+        return;
+    }
+    limit.single_mut().sections[0].value = format!("Up to {} input bits", panel.input_limit);
+
+    let (entity, mut style) = rows.single_mut();
+    commands.entity(entity).despawn_descendants();
+    style.position.top = Val::Px(0.0);
+    let Some(table) = &panel.table else { return };
+    if let Some(output) = panel.karnaugh {
+        commands.entity(entity).with_children(|c| {
+            for line in karnaugh_lines(table, output) {
+                c.spawn(text_builder(&line, &asset_server));
+            }
+        });
+        return;
+    }
+
+    let columns: Vec<_> = table.inputs.iter().chain(&table.outputs).collect();
+    // Buses are widest when written bit by bit
+    let widths: Vec<_> = columns
+        .iter()
+        .map(|column| column.name.chars().count().max(Signal::unknown(column.width).format(column.width).len()))
+        .collect();
+    let line = |fields: Vec<String>| {
+        let mut fields = fields.iter().zip(&widths).map(|(field, &width)| format!("{field:>width$}"));
+        let inputs: Vec<_> = fields.by_ref().take(table.inputs.len()).collect();
+        let outputs: Vec<_> = fields.collect();
+        format!("{} | {}", inputs.join(" "), outputs.join(" "))
+    };
+
+    commands.entity(entity).with_children(|c| {
+        c.spawn(text_builder(&line(columns.iter().map(|column| column.name.clone()).collect()), &asset_server));
+        for row in &table.rows {
+            let inputs =
+                row.inputs.iter().zip(&table.inputs).map(|(&value, column)| Signal::known(value).format(column.width));
+            let outputs = row.outputs.iter().zip(&table.outputs).map(|(value, column)| value.format(column.width));
+            c.spawn(text_builder(&line(inputs.chain(outputs).collect()), &asset_server));
+        }
+    });
+}
+
+/// Minimal sum of products of an output, then its Karnaugh map with the variables of the rows and columns in the
+/// corner
+fn karnaugh_lines(table: &TruthTable, output: usize) -> Vec<String> {
+    let name = &table.outputs[output].name;
+    let mut lines = vec![match table.minimize(output) {
+        Ok(expr) => format!("{name} = {expr}"),
+        Err(e) => format!("{name} can't be minimized: {e}"),
+    }];
+    let map = match KarnaughMap::new(table, output) {
+        Ok(map) => map,
+        Err(e) => {
+            lines.push(format!("No Karnaugh map: {e}"));
+            return lines;
+        }
+    };
+
+    let bits = |value: u64, count: usize| match count {
+        0 => String::new(),
+        count => format!("{value:0count$b}"),
+    };
+    let corner = format!("{}\\{}", map.row_variables.concat(), map.column_variables.concat());
+    let (corner_width, cell_width) = (corner.chars().count(), map.column_variables.len().max(1));
+
+    let column_bits = map.column_variables.len();
+    let columns: Vec<_> =
+        map.column_values.iter().map(|&value| format!("{:>cell_width$}", bits(value, column_bits))).collect();
+    lines.push(format!("{corner} {}", columns.join(" ")));
+    for (&row, cells) in map.row_values.iter().zip(&map.cells) {
+        let cells: Vec<_> = cells.iter().map(|cell| format!("{:>cell_width$}", cell.as_char())).collect();
+        lines.push(format!("{:>corner_width$} {}", bits(row, map.row_variables.len()), cells.join(" ")));
+    }
+    lines
+}
+
+/// Scrolls through the rows with the mouse wheel while the table is hovered
+fn scroll_truth_table(
+    mut scroll: EventReader<MouseWheel>,
+    view: Query<(&Interaction, &Node), With<TableView>>,
+    mut rows: Query<(&mut Style, &Node), With<TableRows>>,
+) {
+    let lines: f32 = scroll
+        .iter()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 20.0,
+        })
+        .sum();
+
+    let (interaction, view) = view.single();
+    if lines == 0.0 || *interaction == Interaction::None {
+        return;
+    }
+
+    let (mut style, rows) = rows.single_mut();
+    let hidden = (rows.size().y - view.size().y).max(0.0);
+    let top = if let Val::Px(top) = style.position.top { top } else { 0.0 };
+    style.position.top = Val::Px((top + lines * SCROLL_STEP).clamp(-hidden, 0.0));
+}
+
+fn export_truth_table(
+    mut events: EventReader<ExportTruthTable>,
+    panel: Res<TablePanel>,
+    mut status: ResMut<FileStatus>,
+) {
+    for ExportTruthTable(path) in events.iter() {
+        let Some(table) = &panel.table else {
+            status.0 = "Generate a truth table before exporting it".to_string();
+            continue;
+        };
+
+        status.0 = match std::fs::write(path, table.to_csv()) {
+            Ok(()) => format!("Exported the truth table to {}", path.display()),
+            Err(e) => format!("Could not export to {}: {e}", path.display()),
+        };
+    }
+}
@@ -0,0 +1,210 @@
+//! Truth tables of circuits, found by letting them settle for every combination of their inputs.
+
+use crate::circuit::{mask, Circuit, Interface, PinId, Signal, Unstable};
+
+/// Number of steps a circuit gets to settle for each combination of inputs
+pub const SETTLE_STEPS: usize = 1000;
+
+/// A named input or output of the circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub width: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub inputs: Vec<u64>,
+    /// Unknown where the circuit didn't settle
+    pub outputs: Vec<Signal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruthTable {
+    pub inputs: Vec<Column>,
+    pub outputs: Vec<Column>,
+    pub rows: Vec<Row>,
+}
+
+/// Returned by [`TruthTable::generate`] when the inputs of the circuit have more bits than allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyInputs {
+    pub bits: u32,
+    pub limit: u32,
+}
+
+impl TruthTable {
+    /// Tries every combination of the inputs of the circuit, counting up with the first input in the highest bits.
+    /// Each one starts from a copy of the circuit as it is, so flip-flops start every row from their current state.
+    /// Combinations are counted on 64 bits, so `max_bits` can't go above 63.
+    pub fn generate(circuit: &Circuit, interface: &Interface, max_bits: u32) -> Result<Self, TooManyInputs> {
+        let columns = |pins: &[(String, _)]| -> Vec<_> {
+            pins.iter().map(|(name, pin)| Column { name: name.clone(), width: circuit.width(*pin) }).collect()
+        };
+        let (inputs, outputs) = (columns(&interface.inputs), columns(&interface.outputs));
+
+        let bits: u32 = inputs.iter().map(|column| column.width as u32).sum();
+        let limit = max_bits.min(u64::BITS - 1);
+        if bits > limit {
+            return Err(TooManyInputs { bits, limit });
+        }
+
+        let widths: Vec<_> = inputs.iter().map(|column| column.width).collect();
+        let output_pins: Vec<_> = interface.outputs.iter().map(|&(_, pin)| pin).collect();
+        let rows = (0..1u64 << bits)
+            .map(|combination| {
+                let values = split_combination(combination, &widths);
+                let pins = interface.inputs.iter().map(|&(_, pin)| pin).zip(values.iter().copied());
+                let outputs = settle(circuit, pins, &output_pins).unwrap_or_else(|Unstable| {
+                    output_pins.iter().map(|&pin| Signal::unknown(circuit.width(pin))).collect()
+                });
+                Row { outputs, inputs: values }
+            })
+            .collect();
+
+        Ok(Self { inputs, outputs, rows })
+    }
+
+    /// Writes the table with a header line of names, single bits as 0, 1, Z or X and buses in hexadecimal
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let header: Vec<_> = self.inputs.iter().chain(&self.outputs).map(|column| csv_field(&column.name)).collect();
+        csv.push_str(&header.join(","));
+        csv.push('\n');
+
+        for row in &self.rows {
+            let inputs = row.inputs.iter().zip(&self.inputs).map(|(&value, column)| (Signal::known(value), column));
+            let outputs = row.outputs.iter().zip(&self.outputs).map(|(&value, column)| (value, column));
+            let fields: Vec<_> = inputs.chain(outputs).map(|(value, column)| value.format(column.width)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Values of inputs of the given widths, the last one taking the lowest bits of the combination
+pub(crate) fn split_combination(combination: u64, widths: &[u8]) -> Vec<u64> {
+    let mut rest = combination;
+    let mut values: Vec<_> = widths
+        .iter()
+        .rev()
+        .map(|&width| {
+            let value = mask(rest, width);
+            rest = rest.checked_shr(width as u32).unwrap_or(0);
+            value
+        })
+        .collect();
+    values.reverse();
+    values
+}
+
+/// Outputs of a copy of the circuit once it settles with the given input values
+pub(crate) fn settle(
+    circuit: &Circuit,
+    inputs: impl Iterator<Item = (PinId, u64)>,
+    outputs: &[PinId],
+) -> Result<Vec<Signal>, Unstable> {
+    let mut circuit = circuit.clone();
+    for (pin, value) in inputs {
+        circuit.set_value(pin, value);
+    }
+    circuit.run_until_stable(SETTLE_STEPS)?;
+    Ok(outputs.iter().map(|&pin| circuit.signal(pin)).collect())
+}
+
+/// Quotes names that would otherwise break the line into several fields
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::GateType;
+
+    /// Adds a one-bit input, a bus input and a one-bit output to a circuit where the output is whether the bus is zero
+    fn bus_is_zero(circuit: &mut Circuit) -> Interface {
+        let (enable, bus, output) = (circuit.add_pin(), circuit.add_bus(2), circuit.add_pin());
+        let nor = circuit.add_gate_with(GateType::Nor, 2, 1);
+        let split = circuit.add_gate_with(GateType::Splitter, 1, 2);
+        let and = circuit.add_gate(GateType::And);
+        let [nor, split, and] = circuit.gates_of([nor, split, and]);
+
+        circuit.connect(bus, split.inputs[0]).unwrap();
+        circuit.connect(split.outputs[0], nor.inputs[0]).unwrap();
+        circuit.connect(split.outputs[1], nor.inputs[1]).unwrap();
+        circuit.connect(nor.outputs[0], and.inputs[0]).unwrap();
+        circuit.connect(enable, and.inputs[1]).unwrap();
+        circuit.connect(and.outputs[0], output).unwrap();
+
+        Interface::named(&[("En", enable), ("Bus", bus)], &[("Zero", output)])
+    }
+
+    #[test]
+    fn enumerates_every_combination() {
+        let mut circuit = Circuit::new();
+        let interface = bus_is_zero(&mut circuit);
+
+        let table = TruthTable::generate(&circuit, &interface, 8).unwrap();
+        assert_eq!(table.inputs[1], Column { name: "Bus".into(), width: 2 });
+        let rows: Vec<_> = table.rows.iter().map(|row| (row.inputs.clone(), row.outputs[0].bits)).collect();
+        assert_eq!(
+            rows,
+            [
+                (vec![0, 0], 0),
+                (vec![0, 1], 0),
+                (vec![0, 2], 0),
+                (vec![0, 3], 0),
+                (vec![1, 0], 1),
+                (vec![1, 1], 0),
+                (vec![1, 2], 0),
+                (vec![1, 3], 0),
+            ]
+        );
+        let csv = table.to_csv();
+        assert_eq!(csv.lines().take(3).collect::<Vec<_>>(), ["En,Bus,Zero", "0,0x0,0", "0,0x1,0"]);
+
+        assert_eq!(TruthTable::generate(&circuit, &interface, 2), Err(TooManyInputs { bits: 3, limit: 2 }));
+    }
+
+    #[test]
+    fn unsettled_outputs_are_unknown() {
+        let mut circuit = Circuit::new();
+        let (input, output) = (circuit.add_pin(), circuit.add_pin());
+        let (not, and) = (circuit.add_gate(GateType::Not), circuit.add_gate(GateType::And));
+        let [not, and] = circuit.gates_of([not, and]);
+        // Oscillates while the input is high
+        circuit.connect(input, and.inputs[0]).unwrap();
+        circuit.connect(not.outputs[0], and.inputs[1]).unwrap();
+        circuit.connect(and.outputs[0], not.inputs[0]).unwrap();
+        circuit.connect(and.outputs[0], output).unwrap();
+        circuit.set(not.inputs[0], false);
+
+        let interface = Interface::named(&[("A", input)], &[("A", output)]);
+        let table = TruthTable::generate(&circuit, &interface, 8).unwrap();
+        assert_eq!(table.rows[1].outputs, [Signal::unknown(1)]);
+        assert_eq!(table.to_csv(), "A,A\n0,0\n1,X\n");
+    }
+
+    #[test]
+    fn combinations_fit_in_64_bits() {
+        assert_eq!(split_combination(u64::MAX, &[64]), [u64::MAX]);
+        assert_eq!(split_combination(0x1ff, &[4, 64]), [0, 0x1ff]);
+
+        let mut circuit = Circuit::new();
+        let bus = circuit.add_bus(64);
+        let interface = Interface::named(&[("A", bus)], &[]);
+        assert_eq!(TruthTable::generate(&circuit, &interface, 64), Err(TooManyInputs { bits: 64, limit: 63 }));
+    }
+
+    #[test]
+    fn quotes_names_with_commas() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("A"), "A");
+    }
+}
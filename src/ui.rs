@@ -2,26 +2,39 @@ use bevy::prelude::*;
 use lazy_static::lazy_static;
 
 use crate::{
-    constants::{Colors, RADIUS},
+    camera::screen_to_world,
+    circuit::{ClockConfig, GateState, GateType, Signal, MAX_WIDTH, VARIADIC_INPUTS},
+    component::Library,
+    constants::{Colors, Depth, RADIUS},
     cursor::Cursor,
-    gate::{GateBundle, GateType, MovingGate},
-    node::NodeSpawner,
+    edge::{Edge, EdgeSignal},
+    gate::{GateSpawner, MovingGate, GATE_SIZE},
+    history::{Edit, EdgeRecord, GateRecord, History, PanelNodeRecord},
+    node::{HoveredNode, Node, NodeSpawner, PinDirection},
 };
 
 pub struct UiBuilder;
 
 impl Plugin for UiBuilder {
     fn build(&self, app: &mut App) {
-        app.add_system(interact_gate_ui)
+        app.insert_resource(PlacedInputs(*VARIADIC_INPUTS.start()))
+            .insert_resource(PlacedWidth(1))
+            .add_system(interact_gate_ui)
+            .add_system(interact_placement_ui)
+            .add_system(show_library)
             .add_startup_system(create_gate_ui)
+            .add_startup_system(create_library_ui)
             .add_startup_system(create_input_ui)
-            .add_system(align_input_nodes)
-            .add_system(interact_remove_input_nodes)
-            .add_system(interact_add_input_nodes);
+            .add_startup_system(create_output_ui)
+            .add_system(align_panel_nodes)
+            .add_system(show_output_values)
+            .add_system(interact_remove_panel_nodes)
+            .add_system(interact_add_panel_nodes)
+            .add_system(edit_panel_node_width);
     }
 }
 
-fn text_builder(text: &str, asset_server: &Res<AssetServer>) -> impl Bundle {
+pub fn text_builder(text: &str, asset_server: &Res<AssetServer>) -> impl Bundle {
     TextBundle::from_section(
         text,
         TextStyle {
@@ -35,15 +48,45 @@ fn text_builder(text: &str, asset_server: &Res<AssetServer>) -> impl Bundle {
 #[derive(Component)]
 struct GateButton(GateType);
 
-fn create_gate_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let button_style = Style {
+/// Panel listing the custom components, grouped by the library they come from
+#[derive(Component)]
+struct LibraryBrowser;
+
+/// Number of inputs given to newly placed gates with a variable number of inputs
+#[derive(Resource)]
+pub struct PlacedInputs(pub usize);
+
+/// Number of bits of newly placed gates, for those that can work on buses
+#[derive(Resource)]
+pub struct PlacedWidth(pub u8);
+
+/// Setting of newly placed gates, changed from the bottom bar
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    Inputs,
+    Width,
+}
+
+/// Changes a setting of placed gates by the given amount
+#[derive(Component)]
+struct PlacementButton(Placement, isize);
+
+#[derive(Component)]
+struct PlacementText(Placement);
+
+pub fn button_style() -> Style {
+    Style {
         size: Size::new(Val::Auto, Val::Px(40.0)),
         margin: UiRect::all(Val::Px(10.0)),
         padding: UiRect::horizontal(Val::Px(10.0)),
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         ..default()
-    };
+    }
+}
+
+fn create_gate_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let button_style = button_style();
 
     commands
         .spawn(NodeBundle {
@@ -65,7 +108,11 @@ fn create_gate_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
         })
         .with_children(|c| {
             use GateType::*;
-            let kinds = [And, Or, Xor, Not];
+            let kinds = [
+                And, Or, Xor, Not, Nand, Nor, Xnor, Buffer, TriState,
+                SrLatch, DFlipFlop, JkFlipFlop, TFlipFlop, Clock(ClockConfig::default()),
+                Splitter, Merger,
+            ];
 
             for kind in kinds {
                 let button_str = kind.as_str();
@@ -81,16 +128,148 @@ fn create_gate_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     c.spawn(text_builder(button_str, &asset_server));
                 });
             }
+
+            let settings = [
+                (Placement::Inputs, format!("{} inputs", VARIADIC_INPUTS.start())),
+                (Placement::Width, "1 bits".to_string()),
+            ];
+            for (placement, text) in settings {
+                for (delta, label) in [(-1, "-"), (1, "+")] {
+                    if delta == 1 {
+                        c.spawn((text_builder(&text, &asset_server), PlacementText(placement)));
+                    }
+
+                    c.spawn((
+                        ButtonBundle {
+                            style: button_style.clone(),
+                            background_color: Colors::OFF.into(),
+                            ..default()
+                        },
+                        PlacementButton(placement, delta),
+                    ))
+                    .with_children(|c| {
+                        c.spawn(text_builder(label, &asset_server));
+                    });
+                }
+            }
         });
 }
 
+fn create_library_ui(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(75.0),
+                    top: Val::Px(60.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: Colors::UI_BG.into(),
+            ..default()
+        },
+        LibraryBrowser,
+    ));
+}
+
+/// Lists the custom components in the library browser, with a row for those made in this circuit and one per library file
+fn show_library(
+    mut commands: Commands,
+    library: Res<Library>,
+    browser: Query<Entity, With<LibraryBrowser>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !library.is_changed() {
+        return;
+    }
+    let Ok(browser) = browser.get_single() else { return };
+
+    let mut sources = vec![];
+    for component in &library.0 {
+        if !sources.contains(&&component.library) {
+            sources.push(&component.library);
+        }
+    }
+
+    commands.entity(browser).despawn_descendants();
+    commands.entity(browser).with_children(|c| {
+        for source in sources {
+            c.spawn(NodeBundle {
+                style: Style {
+                    padding: UiRect::horizontal(Val::Px(10.0)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|c| {
+                let name = source.as_ref().map_or("This circuit".into(), |path| path.display().to_string());
+                c.spawn(text_builder(&name, &asset_server));
+
+                let components = library.0.iter().enumerate().filter(|(_, component)| &component.library == source);
+                for (idx, component) in components {
+                    c.spawn((
+                        ButtonBundle {
+                            style: button_style(),
+                            background_color: Colors::OFF.into(),
+                            ..default()
+                        },
+                        GateButton(GateType::Custom(idx)),
+                    ))
+                    .with_children(|c| {
+                        c.spawn(text_builder(&component.name, &asset_server));
+                    });
+                }
+            });
+        }
+    });
+}
+
+fn interact_placement_ui(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &PlacementButton), Changed<Interaction>>,
+    mut texts: Query<(&mut Text, &PlacementText)>,
+    mut placed_inputs: ResMut<PlacedInputs>,
+    mut placed_width: ResMut<PlacedWidth>,
+) {
+    for (interaction, mut color, &PlacementButton(placement, delta)) in &mut query {
+        match *interaction {
+            Interaction::None => *color = Colors::OFF.into(),
+            Interaction::Hovered => *color = Colors::highlighted(false).into(),
+            Interaction::Clicked => {
+                *color = Colors::ON.into();
+
+                let value = match placement {
+                    Placement::Inputs => {
+                        let count = placed_inputs.0.saturating_add_signed(delta);
+                        placed_inputs.0 = count.clamp(*VARIADIC_INPUTS.start(), *VARIADIC_INPUTS.end());
+                        format!("{} inputs", placed_inputs.0)
+                    }
+                    Placement::Width => {
+                        let width = placed_width.0.saturating_add_signed(delta as i8);
+                        placed_width.0 = width.clamp(1, MAX_WIDTH);
+                        format!("{} bits", placed_width.0)
+                    }
+                };
+
+                let Some((mut text, _)) = texts.iter_mut().find(|(_, text)| text.0 == placement) else { continue };
+                text.sections[0].value = value;
+            }
+        }
+    }
+}
+
 #[allow(clippy::type_complexity)]
 fn interact_gate_ui(
     mut query: Query<(&Interaction, &mut BackgroundColor, &GateButton), Changed<Interaction>>,
     mut moving_gate: ResMut<MovingGate>,
+    mut history: ResMut<History>,
+    placed_inputs: Res<PlacedInputs>,
+    placed_width: Res<PlacedWidth>,
     cursor: Res<Cursor>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut spawner: GateSpawner,
 ) {
     for (interaction, mut color, GateButton(kind)) in &mut query {
         match *interaction {
@@ -99,28 +278,94 @@ fn interact_gate_ui(
             Interaction::Clicked => {
                 *color = Colors::ON.into();
 
-                let gate = GateBundle::new(&asset_server, (*kind).clone(), Vec2::splat(120.0))
-                    .pos(cursor.0);
-                let gate = gate.spawn(&mut commands).id();
+                let gate = spawner.bundle(*kind, GATE_SIZE);
+                let gate = match kind {
+                    // Custom components take their pins from their definition
+                    GateType::Custom(_) => gate,
+                    _ => {
+                        let width_range = kind.width_range();
+                        let width = placed_width.0.clamp(*width_range.start(), *width_range.end());
+                        let num_inputs = if kind.input_range(width) == VARIADIC_INPUTS {
+                            placed_inputs.0
+                        } else {
+                            kind.default_inputs(width)
+                        };
+                        gate.width(width).inputs(num_inputs)
+                    }
+                }
+                .pos(cursor.0);
+                let (size, width) = (gate.size, gate.width);
+                let (gate, inputs, outputs) = gate.spawn(&mut spawner.commands);
 
-                moving_gate.0 = Some((gate, Vec2::ZERO));
+                moving_gate.0 = Some((gate, Vec2::ZERO, true));
+                history.push(Edit::AddGate(GateRecord {
+                    gate,
+                    inputs,
+                    outputs,
+                    kind: *kind,
+                    width,
+                    state: GateState::default(),
+                    pos: cursor.0,
+                    size,
+                    delay: None,
+                }));
             }
         }
     }
 }
 
+/// Side panels holding the nodes through which the circuit talks to the outside world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    Input,
+    Output,
+}
+
+impl Panel {
+    /// Horizontal distance from a remove button to its node, in pixels
+    fn node_offset(self) -> f32 {
+        match self {
+            Panel::Input => 75.0,
+            Panel::Output => -75.0,
+        }
+    }
+}
+
+/// Holds the remove buttons of a panel, in the same order as its nodes
 #[derive(Component)]
-struct RemoveInputRootMarker;
+pub struct PanelRootMarker(pub Panel);
 
 #[derive(Component)]
-pub struct AddInputMarker;
+pub struct AddNodeMarker(pub Panel);
 
 #[derive(Component)]
-pub struct RemoveInputMarker(Entity);
+pub struct RemoveNodeMarker(pub Entity);
 
 #[derive(Component)]
 pub struct InputNodeMarker;
 
+#[derive(Component)]
+pub struct OutputNodeMarker;
+
+/// Name of a node of a side panel, shown next to it
+#[derive(Component)]
+pub struct NodeLabel(pub String);
+
+impl NodeLabel {
+    /// Value of an input node named after a constant, which drives it and can't be changed by hand
+    pub fn constant(&self) -> Option<Signal> {
+        match self.0.as_str() {
+            "0" => Some(Signal::known(0)),
+            "1" => Some(Signal::known(1)),
+            _ => None,
+        }
+    }
+}
+
+/// Text showing the label of a panel node
+#[derive(Component)]
+struct NodeLabelText;
+
 lazy_static! {
     static ref INPUT_BUTTON_STYLE: Style = Style {
         size: Size::new(Val::Px(RADIUS*2.0), Val::Px(RADIUS*2.0)),
@@ -129,12 +374,17 @@ lazy_static! {
     };
 }
 
-fn create_input_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn create_panel_ui(commands: &mut Commands, asset_server: &Res<AssetServer>, panel: Panel) {
+    let position = match panel {
+        Panel::Input => UiRect::left(Val::Px(0.0)),
+        Panel::Output => UiRect::right(Val::Px(0.0)),
+    };
+
     commands
         .spawn(NodeBundle {
             style: Style {
                 position_type: PositionType::Absolute,
-                position: UiRect::left(Val::Px(0.0)),
+                position,
                 size: Size::new(Val::Px(75.0), Val::Percent(100.0)),
                 padding: UiRect::all(Val::Px(10.0)),
                 justify_content: JustifyContent::Center,
@@ -154,7 +404,7 @@ fn create_input_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     },
                     ..default()
                 },
-                RemoveInputRootMarker
+                PanelRootMarker(panel)
             ));
 
             c.spawn((
@@ -163,50 +413,94 @@ fn create_input_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     image: asset_server.load("add_input_node.png").into(),
                     ..default()
                 },
-                AddInputMarker
+                AddNodeMarker(panel)
             ));
         });
 }
 
+fn create_input_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    create_panel_ui(&mut commands, &asset_server, Panel::Input);
+}
+
+fn create_output_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    create_panel_ui(&mut commands, &asset_server, Panel::Output);
+}
+
 /// This needed a bit of hackery to translate screen space coordinates into world space coordinates,
 /// as it was not possible to simply put the nodes as childs of the ui
-fn align_input_nodes(
-    buttons: Query<(&GlobalTransform, &RemoveInputMarker)>,
-    mut nodes: Query<&mut Transform, With<InputNodeMarker>>,
+#[allow(clippy::type_complexity)]
+fn align_panel_nodes(
+    buttons: Query<(&GlobalTransform, &RemoveNodeMarker, &Parent)>,
+    roots: Query<&PanelRootMarker>,
+    mut nodes: Query<&mut Transform, Or<(With<InputNodeMarker>, With<OutputNodeMarker>)>>,
     windows: Res<Windows>,
-    camera_transform: Query<&Transform, (With<Camera>, Without<InputNodeMarker>)>,
+    camera: Query<(&Transform, &OrthographicProjection), (With<Camera>, Without<Node>)>,
 ) {
     let wnd = windows.get_primary().unwrap();
-    let camera_transform = camera_transform.get_single().unwrap();
+    let (camera_transform, projection) = camera.get_single().unwrap();
 
-    for (transform, &RemoveInputMarker(node)) in buttons.iter() {
-        let position = transform.translation();
-        let norm = Vec3::new(
-            position.x - wnd.width() / 2.0,
-            wnd.height() / 2.0 - position.y,
-            0.0,
-        );
+    for (transform, &RemoveNodeMarker(node), parent) in buttons.iter() {
+        let Ok(&PanelRootMarker(panel)) = roots.get(parent.get()) else { continue };
 
-        let world = *camera_transform * norm;
+        let position = transform.translation();
+        // Ui coordinates start from the top of the window, and the node sits next to the panel
+        let screen = Vec2::new(position.x + panel.node_offset(), wnd.height() - position.y);
 
         let mut node_transform = nodes.get_mut(node).unwrap();
 
-        let new_transform = world.truncate() + Vec2::new(75.0, 0.0);
+        let new_transform = screen_to_world(wnd, camera_transform, projection, screen);
 
         node_transform.translation.x = new_transform.x;
         node_transform.translation.y = new_transform.y;
     }
 }
 
-fn interact_remove_input_nodes(
+/// Output nodes show their value next to their label
+fn show_output_values(
+    nodes: Query<(&Node, &NodeLabel, &Children), With<OutputNodeMarker>>,
+    mut texts: Query<&mut Text, With<NodeLabelText>>,
+) {
+    for (node, label, children) in nodes.iter() {
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = format!("{} = {}", label.0, node.value.format(node.width));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn interact_remove_panel_nodes(
     mut commands: Commands,
-    mut buttons: Query<(Entity, &mut BackgroundColor, &Interaction, &RemoveInputMarker), Changed<Interaction>>
+    mut buttons: Query<(Entity, &mut BackgroundColor, &Interaction, &RemoveNodeMarker, &Parent), Changed<Interaction>>,
+    roots: Query<(&PanelRootMarker, &Children)>,
+    nodes: Query<(&Node, &NodeLabel)>,
+    edges: Query<(Entity, &Edge, &EdgeSignal)>,
+    mut history: ResMut<History>,
 ) {
-    for (entity, mut color, interaction, &RemoveInputMarker(node)) in &mut buttons {
+    for (entity, mut color, interaction, &RemoveNodeMarker(node), parent) in &mut buttons {
         match interaction {
             Interaction::None => color.0 = Colors::OFF,
             Interaction::Hovered => color.0 = Colors::highlighted(false),
             Interaction::Clicked => {
+                let (&PanelRootMarker(panel), children) = roots.get(parent.get()).unwrap();
+                let index = children.iter().position(|&child| child == entity).unwrap();
+                let (&Node { value, width }, label) = nodes.get(node).unwrap();
+                let attached = edges
+                    .iter()
+                    .filter(|(_, edge, _)| edge.from == node || edge.to == node)
+                    .map(|(edge, &Edge { from, to }, signal)| EdgeRecord { edge, from, to, delay: signal.delay })
+                    .collect();
+
+                history.push(Edit::RemovePanelNode(PanelNodeRecord {
+                    panel,
+                    node,
+                    index,
+                    label: label.0.clone(),
+                    value,
+                    width,
+                    edges: attached,
+                }));
+
                 commands.get_entity(node).unwrap().despawn_recursive();
                 commands.get_entity(entity).unwrap().despawn_recursive();
             }
@@ -214,30 +508,171 @@ fn interact_remove_input_nodes(
     }
 }
 
-fn interact_add_input_nodes(
+/// Returns the first free label for a node of the given panel.
+/// Inputs are named A, B, ..., Z, A1, B1, ..., and outputs Y0, Y1, ...
+pub fn next_label(panel: Panel, used: &[&str]) -> String {
+    (0..)
+        .map(|n: usize| {
+            let letter = (b'A' + (n % 26) as u8) as char;
+            match (panel, n / 26) {
+                (Panel::Input, 0) => letter.to_string(),
+                (Panel::Input, suffix) => format!("{letter}{suffix}"),
+                (Panel::Output, _) => format!("Y{n}"),
+            }
+        })
+        .find(|label| !used.contains(&label.as_str()))
+        .unwrap()
+}
+
+/// Spawns a node in a side panel at the given position (or at the bottom), along with the button that removes it
+pub fn spawn_panel_node(
+    commands: &mut Commands,
+    panel: Panel,
+    root: Entity,
+    asset_server: &Res<AssetServer>,
+    index: Option<usize>,
+    label: String,
+    node: Node,
+) -> Entity {
+    let direction = match panel {
+        Panel::Input => PinDirection::Driver,
+        Panel::Output => PinDirection::Sink,
+    };
+    let mut spawner = NodeSpawner::new().direction(direction);
+    spawner.node = node;
+
+    // Labels go on the side facing the canvas
+    let (horizontal, x) = match panel {
+        Panel::Input => (HorizontalAlign::Left, RADIUS * 0.7),
+        Panel::Output => (HorizontalAlign::Right, -RADIUS * 0.7),
+    };
+
+    let text = Text2dBundle {
+        text: Text::from_section(
+            label.clone(),
+            TextStyle {
+                font: asset_server.load("FiraCode.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_alignment(TextAlignment {
+            horizontal,
+            vertical: VerticalAlign::Bottom,
+        }),
+        transform: Transform::from_xyz(x, RADIUS * 0.7, Depth::TEXT - Depth::NODE),
+        ..default()
+    };
+
+    let mut node = commands.spawn((spawner, NodeLabel(label)));
+    match panel {
+        Panel::Input => node.insert(InputNodeMarker),
+        Panel::Output => node.insert(OutputNodeMarker),
+    };
+    let node = node
+        .with_children(|b| {
+            b.spawn((text, NodeLabelText));
+        })
+        .id();
+
+    let remove_button = commands.spawn((
+        ButtonBundle {
+            style: INPUT_BUTTON_STYLE.clone(),
+            image: asset_server.load("remove_input_node.png").into(),
+            ..default()
+        },
+        RemoveNodeMarker(node)
+    )).id();
+    match index {
+        Some(index) => commands.get_entity(root).unwrap().insert_children(index, &[remove_button]),
+        None => commands.get_entity(root).unwrap().add_child(remove_button),
+    };
+
+    node
+}
+
+#[allow(clippy::type_complexity)]
+fn interact_add_panel_nodes(
     mut commands: Commands,
-    mut buttons: Query<(&mut BackgroundColor, &Interaction), (Changed<Interaction>, With<AddInputMarker>)>,
-    root: Query<Entity, With<RemoveInputRootMarker>>,
+    mut buttons: Query<(&mut BackgroundColor, &Interaction, &AddNodeMarker), Changed<Interaction>>,
+    roots: Query<(Entity, &PanelRootMarker, Option<&Children>)>,
+    labels: Query<&NodeLabel>,
+    mut history: ResMut<History>,
     asset_server: Res<AssetServer>
 ) {
-    let root = root.get_single().unwrap();
-
-    for (mut color, interaction) in &mut buttons {
+    for (mut color, interaction, &AddNodeMarker(panel)) in &mut buttons {
         match interaction {
             Interaction::None => color.0 = Colors::OFF,
             Interaction::Hovered => color.0 = Colors::highlighted(false),
             Interaction::Clicked => {
-                let node = commands.spawn((NodeSpawner::new(), InputNodeMarker)).id();
-                let remove_button = commands.spawn((
-                    ButtonBundle {
-                        style: INPUT_BUTTON_STYLE.clone(),
-                        image: asset_server.load("remove_input_node.png").into(),
-                        ..default()
-                    },
-                    RemoveInputMarker(node)
-                )).id();
-                commands.get_entity(root).unwrap().add_child(remove_button);
+                let (root, _, children) = roots.iter().find(|(_, root, _)| root.0 == panel).unwrap();
+
+                let used: Vec<_> = labels.iter().map(|label| label.0.as_str()).collect();
+                let label = next_label(panel, &used);
+                // Inputs drive the circuit, outputs float until something is connected to them
+                let value = match panel {
+                    Panel::Input => Signal::known(0),
+                    Panel::Output => Signal::floating(1),
+                };
+                let node = Node { value, width: 1 };
+                let node = spawn_panel_node(&mut commands, panel, root, &asset_server, None, label.clone(), node);
+
+                let index = children.map_or(0, |children| children.len());
+                history.push(Edit::AddPanelNode(PanelNodeRecord {
+                    panel,
+                    node,
+                    index,
+                    label,
+                    value,
+                    width: 1,
+                    edges: vec![],
+                }));
             }
         }
     }
 }
+
+/// Changes the number of bits of the panel node under the mouse with the up and down arrows.
+/// Its edges are removed, since they now connect pins of different widths.
+#[allow(clippy::type_complexity)]
+fn edit_panel_node_width(
+    mut commands: Commands,
+    mut nodes: Query<(&mut Node, &NodeLabel), Or<(With<InputNodeMarker>, With<OutputNodeMarker>)>>,
+    edges: Query<(Entity, &Edge, &EdgeSignal)>,
+    hovered: Res<HoveredNode>,
+    mut history: ResMut<History>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let delta = if keys.just_pressed(KeyCode::Up) {
+        1
+    } else if keys.just_pressed(KeyCode::Down) {
+        -1
+    } else {
+        return;
+    };
+
+    let Some(entity) = hovered.0 else { return };
+    let Ok((mut node, label)) = nodes.get_mut(entity) else { return };
+    if label.constant().is_some() {
+        return;
+    }
+
+    let width = node.width.saturating_add_signed(delta).clamp(1, MAX_WIDTH);
+    if width == node.width {
+        return;
+    }
+
+    let mut edits: Vec<_> = edges
+        .iter()
+        .filter(|(_, edge, _)| edge.from == entity || edge.to == entity)
+        .map(|(edge, &Edge { from, to }, signal)| {
+            commands.entity(edge).despawn();
+            Edit::RemoveEdge(EdgeRecord { edge, from, to, delay: signal.delay })
+        })
+        .collect();
+    edits.push(Edit::ResizeNode { node: entity, from: node.width, to: width });
+    history.push(Edit::Batch(edits));
+
+    node.width = width;
+    node.value = node.value.mask(width);
+}